@@ -1,5 +1,14 @@
+use chrono::NaiveDate;
 use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
 use clap::ValueHint;
+use serde::Deserialize;
+use unicode_width::UnicodeWidthChar;
+
+use crate::keys::Preset;
+use crate::rules::Mode;
+use crate::theme::{ColorMode, Glyphs};
 
 #[derive(Parser, Debug, Clone)]
 #[command(
@@ -10,11 +19,11 @@ use clap::ValueHint;
 )]
 pub struct Args {
     /// Disable ghost piece shown at the bottom of the board
-    #[clap(short, long, action)]
+    #[clap(short, long, action, env = "TET_RIS_GHOST")]
     pub ghost: bool,
 
     /// Disable hold piece with 'c' key
-    #[clap(short = 'c', long = "hold", action)]
+    #[clap(short = 'c', long = "hold", action, env = "TET_RIS_HOLD")]
     pub hold: bool,
 
     /// Gravity speed for the game
@@ -22,27 +31,277 @@ pub struct Args {
         short = 't',
         long = "tick",
         default_value = "10",
-        value_name = "MILLISECONDS"
+        value_name = "MILLISECONDS",
+        env = "TET_RIS_TICK"
     )]
     pub gravity: u64,
 
-    /// Path to save file [default: save.tetris]
-    #[clap(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    /// Path to save file
+    #[clap(short, long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath, env = "TET_RIS_SAVE")]
     pub save: Option<String>,
 
-    /// Characters to use for tetrominoes
-    #[clap(long, value_name = "2 CHARACTERS", default_value = "██")]
+    /// Named save slot to load and save [default: pick at startup]
+    #[clap(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with = "save",
+        value_parser = parse_slot,
+        env = "TET_RIS_SLOT"
+    )]
+    pub slot: Option<String>,
+
+    /// Characters to use for tetrominoes, two that are one column wide each
+    #[clap(
+        long,
+        value_name = "2 CHARACTERS",
+        default_value = "██",
+        value_parser = parse_chars,
+        env = "TET_RIS_CHARS"
+    )]
     pub chars: String,
 
     /// Disable colors
-    #[clap(long = "no-colors", action)]
+    #[clap(long = "no-colors", action, env = "TET_RIS_NO_COLORS")]
     pub no_colors: bool,
 
     /// Return to 1984 Tetris
-    #[clap(short, long = "original", action)]
+    #[clap(short, long = "original", action, env = "TET_RIS_ORIGINAL")]
     pub og: bool,
 
     /// Play Sirtet (Upside-down Tetris)
-    #[clap(long = "sirtet", action)]
+    #[clap(long = "sirtet", action, env = "TET_RIS_SIRTET")]
     pub sirtet: bool,
+
+    /// Show a statistics panel next to the board
+    #[clap(long, action, env = "TET_RIS_STATS")]
+    pub stats: bool,
+
+    /// Delayed auto shift: how long a held left/right key waits before repeating, on top of the terminal's own repeat delay
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "0",
+        env = "TET_RIS_DAS"
+    )]
+    pub das: u64,
+
+    /// Auto repeat rate: least time between moves of a held left/right key; they can't come faster than the terminal repeats the key, so 0 keeps its rate
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "0",
+        env = "TET_RIS_ARR"
+    )]
+    pub arr: u64,
+
+    /// Rows moved per soft drop
+    #[clap(
+        long,
+        value_name = "FACTOR",
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "TET_RIS_SOFT_DROP"
+    )]
+    pub soft_drop: u64,
+
+    /// How long a piece can rest on the stack before it locks
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "0",
+        env = "TET_RIS_LOCK_DELAY"
+    )]
+    pub lock_delay: u64,
+
+    /// How long full rows stay on the board before they are cleared, while the game waits
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "0",
+        env = "TET_RIS_LINE_CLEAR_DELAY"
+    )]
+    pub line_clear_delay: u64,
+
+    /// Animation shown for full rows during the line clear delay
+    #[clap(
+        long,
+        value_enum,
+        default_value = "flash",
+        env = "TET_RIS_CLEAR_ANIMATION"
+    )]
+    pub clear_animation: ClearAnimation,
+
+    /// How long a piece flashes after it locks; 0 for no flash
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "0",
+        env = "TET_RIS_LOCK_FLASH"
+    )]
+    pub lock_flash: u64,
+
+    /// How long action text (TETRIS, T-SPIN, COMBO, B2B, ALL CLEAR) stays next to the board; 0 for none
+    #[clap(
+        long,
+        value_name = "MILLISECONDS",
+        default_value = "1500",
+        env = "TET_RIS_POPUP_TIME"
+    )]
+    pub popup_time: u64,
+
+    /// Key binding preset; single actions can be rebound in the config file
+    #[clap(long, value_enum, default_value = "default", env = "TET_RIS_KEYS")]
+    pub keys: Preset,
+
+    /// Colour theme: guideline, nes, gameboy, monochrome, solarized, the colour-blind safe okabe-ito and tol, or one defined in the config file
+    #[clap(long, default_value = "guideline", env = "TET_RIS_THEME")]
+    pub theme: String,
+
+    /// Colours the terminal can show; auto looks at NO_COLOR, COLORTERM and TERM
+    #[clap(long, value_enum, default_value = "auto", env = "TET_RIS_COLOR_MODE")]
+    pub color_mode: ColorMode,
+
+    /// Draw each piece with its own pattern or letter, so pieces can be told apart without colour
+    #[clap(long, value_enum, default_value = "off", env = "TET_RIS_GLYPHS")]
+    pub glyphs: Glyphs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Watch a recorded game
+    #[command(
+        long_about = "Watch a recorded game\n\nControls: 'p' to pause, Down arrow to step while paused, 'f' to cycle speed (1x/2x/4x/max), Left and Right arrow keys to seek to the previous or next piece, and 'q' to quit"
+    )]
+    Replay {
+        /// Replay file to watch
+        #[clap(value_name = "FILE", value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// Write every frame to this file as plain text instead of playing the replay on the terminal ('-' for standard output)
+        #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        frames: Option<String>,
+    },
+
+    /// Export or import a save as JSON or TOML
+    Save {
+        #[command(subcommand)]
+        action: SaveCommand,
+    },
+
+    /// Show the high score tables
+    Scores {
+        /// Only show this mode [default: all modes]
+        #[clap(long, value_enum)]
+        mode: Option<Mode>,
+    },
+
+    /// List, chart or export finished games
+    History {
+        /// Only games of this mode
+        #[clap(long, value_enum)]
+        mode: Option<Mode>,
+
+        /// Only games played on or after this day
+        #[clap(long, value_name = "YYYY-MM-DD", value_parser = parse_date)]
+        since: Option<NaiveDate>,
+
+        /// Only the most recent games
+        #[clap(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// Write the games to a file instead of listing them
+        #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        export: Option<String>,
+
+        /// Export format [default: from the file extension, else csv]
+        #[clap(long, value_enum, requires = "export")]
+        format: Option<HistoryFormat>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SaveCommand {
+    /// Write a save (--slot, --save, or the latest slot) to a readable file
+    Export {
+        /// File to write
+        #[clap(value_name = "FILE", value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// File format [default: from the file extension, else json]
+        #[clap(long, value_enum)]
+        format: Option<Format>,
+    },
+
+    /// Check a readable file and store it as a save (--slot, --save, or a slot named after the file)
+    Import {
+        /// File to read
+        #[clap(value_name = "FILE", value_hint = ValueHint::FilePath)]
+        file: String,
+
+        /// File format [default: from the file extension, else json]
+        #[clap(long, value_enum)]
+        format: Option<Format>,
+
+        /// Replace an existing save
+        #[clap(long, action)]
+        force: bool,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+// how full rows are drawn while they wait to be cleared
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClearAnimation {
+    // left as they are
+    Off,
+    // blinking
+    Flash,
+    // emptied from the middle outwards
+    Collapse,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| e.to_string())
+}
+
+pub fn parse_slot(name: &str) -> Result<String, String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(name.to_string())
+    } else {
+        Err("slot names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+// a board cell is two columns wide, and the screen is drawn as if every
+// character took one
+pub fn parse_chars(chars: &str) -> Result<String, String> {
+    if chars.chars().count() == 2
+        && chars
+            .chars()
+            .all(|c| !c.is_control() && c.width() == Some(1))
+    {
+        Ok(chars.to_string())
+    } else {
+        Err("must be 2 characters, each one column wide".to_string())
+    }
 }
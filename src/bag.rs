@@ -1,23 +1,104 @@
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-use serde::{Deserialize, Serialize};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Serialize, Deserialize, Clone, Hash)]
+const PIECES: [char; 7] = ['I', 'J', 'L', 'O', 'S', 'T', 'Z'];
+
+// 7-bag randomizer driven by its own seeded generator so that a game
+// (and its replay) can be reproduced exactly from the seed
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq)]
 pub struct Bag {
     pieces: Vec<char>,
+    #[serde(with = "text_u64")]
+    seed: u64,
+    #[serde(with = "text_u64")]
+    state: u64,
 }
 
 impl Bag {
     pub fn new() -> Self {
-        let mut pieces = vec!['I', 'J', 'L', 'O', 'S', 'T', 'Z'];
-        pieces.shuffle(&mut thread_rng());
-        Bag { pieces }
+        Bag::seeded(thread_rng().gen())
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        let mut bag = Bag {
+            pieces: Vec::new(),
+            seed,
+            state: seed,
+        };
+        bag.refill();
+        bag
+    }
+
+    // continue a bag from a save that predates seeded bags
+    pub fn resume(pieces: Vec<char>) -> Self {
+        let seed = thread_rng().gen();
+        Bag {
+            pieces,
+            seed,
+            state: seed,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // at most one full bag of known pieces
+    pub fn is_valid(&self) -> bool {
+        self.pieces.len() <= PIECES.len()
+            && PIECES
+                .iter()
+                .all(|p| self.pieces.iter().filter(|&q| q == p).count() <= 1)
+            && self.pieces.iter().all(|p| PIECES.contains(p))
     }
 
     pub fn draw(&mut self) -> char {
         if self.pieces.is_empty() {
-            *self = Bag::new();
+            self.refill();
         }
         self.pieces.pop().unwrap()
     }
+
+    fn refill(&mut self) {
+        self.pieces = PIECES.to_vec();
+
+        // Fisher-Yates shuffle
+        for i in (1..self.pieces.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            self.pieces.swap(i, j);
+        }
+    }
+
+    // splitmix64
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+// TOML integers are signed 64-bit, so the generator state is written as a
+// string in text formats; binary saves keep the plain u64
+mod text_u64 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&value.to_string())
+        } else {
+            serializer.serialize_u64(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        } else {
+            u64::deserialize(deserializer)
+        }
+    }
 }
@@ -0,0 +1,164 @@
+use clap::{parser::ValueSource, ArgMatches};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error, ErrorKind, Result},
+};
+use toml_edit::{value, Array, DocumentMut, Item, Table, TomlError};
+
+use crate::args::{parse_chars, parse_slot, Args, ClearAnimation};
+use crate::keys::{Bindings, Preset};
+use crate::paths::config_path;
+use crate::theme::{ColorMode, Glyphs, ThemeConfig};
+
+// config.toml in the config directory; every key mirrors the command line
+// option of the same name, which (like its environment variable) wins over
+// the file
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    ghost: Option<bool>,
+    hold: Option<bool>,
+    tick: Option<u64>,
+    save: Option<String>,
+    slot: Option<String>,
+    chars: Option<String>,
+    no_colors: Option<bool>,
+    original: Option<bool>,
+    sirtet: Option<bool>,
+    stats: Option<bool>,
+    das: Option<u64>,
+    arr: Option<u64>,
+    soft_drop: Option<u64>,
+    lock_delay: Option<u64>,
+    line_clear_delay: Option<u64>,
+    clear_animation: Option<ClearAnimation>,
+    lock_flash: Option<u64>,
+    popup_time: Option<u64>,
+    keys: Option<Preset>,
+    theme: Option<String>,
+    color_mode: Option<ColorMode>,
+    glyphs: Option<Glyphs>,
+    // per-action overrides of the key preset
+    #[serde(default)]
+    pub bindings: Bindings,
+    // user-defined themes by name
+    #[serde(default)]
+    pub themes: BTreeMap<String, ThemeConfig>,
+}
+
+pub fn load() -> Result<Config> {
+    let text = match fs::read_to_string(config_path()) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e),
+    };
+    toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+// true if the option wasn't given on the command line or in the environment
+fn unset(matches: &ArgMatches, id: &str) -> bool {
+    !matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+fn set<T>(matches: &ArgMatches, id: &str, target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        if unset(matches, id) {
+            *target = value;
+        }
+    }
+}
+
+impl Config {
+    // fill in the options that were left at their defaults
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+
+        if let Some(slot) = &self.slot {
+            parse_slot(slot).map_err(|e| invalid(format!("slot: {}", e)))?;
+        }
+        if let Some(chars) = &self.chars {
+            parse_chars(chars).map_err(|e| invalid(format!("chars: {}", e)))?;
+        }
+        if self.soft_drop == Some(0) {
+            return Err(invalid("soft-drop must be at least 1".to_string()));
+        }
+        if self.save.is_some() && self.slot.is_some() {
+            return Err(invalid("save and slot can't both be set".to_string()));
+        }
+
+        set(matches, "ghost", &mut args.ghost, self.ghost);
+        set(matches, "hold", &mut args.hold, self.hold);
+        set(matches, "gravity", &mut args.gravity, self.tick);
+        set(matches, "chars", &mut args.chars, self.chars.clone());
+        set(matches, "no_colors", &mut args.no_colors, self.no_colors);
+        set(matches, "og", &mut args.og, self.original);
+        set(matches, "sirtet", &mut args.sirtet, self.sirtet);
+        set(matches, "stats", &mut args.stats, self.stats);
+        set(matches, "das", &mut args.das, self.das);
+        set(matches, "arr", &mut args.arr, self.arr);
+        set(matches, "soft_drop", &mut args.soft_drop, self.soft_drop);
+        set(matches, "lock_delay", &mut args.lock_delay, self.lock_delay);
+        set(
+            matches,
+            "line_clear_delay",
+            &mut args.line_clear_delay,
+            self.line_clear_delay,
+        );
+        set(
+            matches,
+            "clear_animation",
+            &mut args.clear_animation,
+            self.clear_animation,
+        );
+        set(matches, "lock_flash", &mut args.lock_flash, self.lock_flash);
+        set(matches, "popup_time", &mut args.popup_time, self.popup_time);
+        set(matches, "keys", &mut args.keys, self.keys);
+        set(matches, "theme", &mut args.theme, self.theme.clone());
+        set(matches, "color_mode", &mut args.color_mode, self.color_mode);
+        set(matches, "glyphs", &mut args.glyphs, self.glyphs);
+
+        // a save file or slot picked on the command line replaces both
+        if unset(matches, "save") && unset(matches, "slot") {
+            if self.save.is_some() {
+                args.save = self.save.clone();
+            }
+            if self.slot.is_some() {
+                args.slot = self.slot.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+// replace the [bindings] table, keeping the rest of the file as it was
+pub fn save_bindings(bindings: &Bindings) -> Result<()> {
+    let path = config_path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut doc: DocumentMut = text
+        .parse()
+        .map_err(|e: TomlError| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+    if bindings.is_empty() {
+        doc.remove("bindings");
+    } else {
+        let mut table = Table::new();
+        for (action, keys) in bindings {
+            table.insert(action, value(Array::from_iter(keys)));
+        }
+        doc.insert("bindings", Item::Table(table));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, doc.to_string())
+}
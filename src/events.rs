@@ -0,0 +1,199 @@
+// a line clear (or a t-spin without one) worth calling out on the HUD
+#[derive(Clone, Hash, Debug, PartialEq)]
+pub struct Event {
+    pub lines: usize,
+    pub tspin: bool,
+    // pieces in a row that cleared lines, counting this one
+    pub combo: usize,
+    // a tetris or t-spin clear right after another one
+    pub b2b: bool,
+    // nothing left on the board
+    pub all_clear: bool,
+    pub points: usize,
+}
+
+impl Event {
+    fn is_special(&self) -> bool {
+        self.tspin || self.lines == 4 || self.combo > 1 || self.b2b || self.all_clear
+    }
+}
+
+// engine events since they were last taken, and what the engine needs to
+// recognise them; none of it changes how the game plays out, so it isn't
+// saved
+#[derive(Clone, Hash, Default)]
+pub struct Events {
+    list: Vec<Event>,
+    // the last move of the active piece was a rotation
+    pub rotated: bool,
+    // the piece that locked last was a t-spin
+    pub tspin: bool,
+    // the last line clear was a tetris or a t-spin, for back-to-back
+    pub difficult: bool,
+}
+
+impl Events {
+    // only events worth a pop-up are kept
+    pub fn push(&mut self, event: Event) {
+        if event.is_special() {
+            self.list.push(event);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamestate::GameState;
+    use crate::input::Action;
+    use crate::rules::Rules;
+    use crate::tetlib::{init, new_piece, tick};
+    use crate::tetrominoe::{State, TColor, Tetrominoe};
+    use crate::{HEIGHT, WIDTH};
+
+    const RULES: Rules = Rules {
+        hold: false,
+        ghost: false,
+        lock_delay: 0,
+        soft_drop: 1,
+        line_clear_delay: 0,
+    };
+
+    // `rows` at the bottom of the board: '#' for landed blocks and 'T' for
+    // the active piece, a T
+    fn set_board(gs: &mut GameState, rows: &[&str]) {
+        gs.display = init(WIDTH, HEIGHT);
+        for (row, line) in (HEIGHT - rows.len()..).zip(rows) {
+            for (col, c) in line.chars().enumerate() {
+                gs.display[row][col] = match c {
+                    '#' => Tetrominoe::new(Some(State::Landed), Some(TColor::Blue)),
+                    'T' => Tetrominoe::new(Some(State::Active), Some(TColor::Magenta)),
+                    _ => Tetrominoe::default(),
+                };
+            }
+        }
+        gs.active_piece = Tetrominoe::from('T', None);
+    }
+
+    // the same board with an I spawned at the top, standing up in column 5
+    fn set_board_with_i(gs: &mut GameState, rows: &[&str]) {
+        set_board(gs, rows);
+        new_piece(gs, Some('I'));
+    }
+
+    fn play(gs: &mut GameState, actions: &[Action]) -> Vec<Event> {
+        assert!(!tick(gs, actions, &RULES));
+        gs.events.take()
+    }
+
+    fn event(lines: usize, points: usize) -> Event {
+        Event {
+            lines,
+            tspin: false,
+            combo: 1,
+            b2b: false,
+            all_clear: false,
+            points,
+        }
+    }
+
+    // a T turned down into a slot: the engine only turns pieces with nothing
+    // landed in their 4x4 box, so it is set down as if it just had been
+    const TSPIN_DOUBLE: [&str; 3] = ["#.........", "TTT#######", "#T########"];
+
+    #[test]
+    fn tspin_and_back_to_back() {
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        set_board(&mut gs, &TSPIN_DOUBLE);
+        gs.events.rotated = true;
+        let tspin = Event {
+            tspin: true,
+            ..event(2, 100)
+        };
+        assert_eq!(play(&mut gs, &[Action::HardDrop]), [tspin]);
+
+        // a tetris right after is back to back, and the second clear in a row
+        set_board_with_i(
+            &mut gs,
+            &[
+                ".........#",
+                ".#########",
+                ".#########",
+                ".#########",
+                ".#########",
+            ],
+        );
+        let tetris = Event {
+            combo: 2,
+            b2b: true,
+            ..event(4, 1200)
+        };
+        assert_eq!(
+            play(
+                &mut gs,
+                &[vec![Action::Left; 5], vec![Action::HardDrop]].concat()
+            ),
+            [tetris]
+        );
+        assert_eq!(gs.stats.combo, 2);
+    }
+
+    #[test]
+    fn tspin_needs_a_turn_and_three_corners() {
+        // without lines it is still called out
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        set_board(&mut gs, &["#.........", "TTT.......", "#T#......."]);
+        gs.events.rotated = true;
+        let tspin = Event {
+            tspin: true,
+            combo: 0,
+            ..event(0, 0)
+        };
+        assert_eq!(play(&mut gs, &[Action::HardDrop]), [tspin]);
+
+        // moved in rather than turned
+        set_board(&mut gs, &TSPIN_DOUBLE);
+        assert_eq!(play(&mut gs, &[Action::HardDrop]), []);
+        assert!(!gs.events.difficult);
+
+        // only two corners filled
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        set_board(&mut gs, &[".........#", "TTT#######", "#T########"]);
+        gs.events.rotated = true;
+        assert_eq!(play(&mut gs, &[Action::HardDrop]), []);
+        assert_eq!(gs.gamescore.lines, 2);
+    }
+
+    #[test]
+    fn combos_and_all_clears() {
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        let drop_right = [vec![Action::Right; 4], vec![Action::HardDrop]].concat();
+
+        // a single on its own isn't worth a pop-up, the second in a row is
+        set_board_with_i(&mut gs, &["#.........", "#########."]);
+        assert_eq!(play(&mut gs, &drop_right), []);
+        set_board_with_i(&mut gs, &["#.........", "#########."]);
+        let combo = Event {
+            combo: 2,
+            ..event(1, 40)
+        };
+        assert_eq!(play(&mut gs, &drop_right), [combo]);
+
+        // a piece that clears nothing ends the combo
+        set_board_with_i(&mut gs, &["#........."]);
+        assert_eq!(play(&mut gs, &drop_right), []);
+        assert_eq!(gs.stats.combo, 0);
+
+        // clearing everything off the board
+        set_board_with_i(&mut gs, &["#########."; 4]);
+        let all_clear = Event {
+            all_clear: true,
+            ..event(4, 1200)
+        };
+        assert_eq!(play(&mut gs, &drop_right), [all_clear]);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+use crate::args::{Args, Format};
+use crate::gamestate::GameState;
+use crate::paths::{save_path, slot_path};
+use crate::slots;
+
+fn format_for(file: &Path, format: Option<Format>) -> Format {
+    format.unwrap_or_else(|| match file.extension() {
+        Some(ext) if ext == "toml" => Format::Toml,
+        _ => Format::Json,
+    })
+}
+
+fn invalid<E: std::fmt::Display>(e: E) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+// write a save as JSON or TOML; returns the save that was exported
+pub fn export(args: &Args, file: &Path, format: Option<Format>) -> Result<PathBuf> {
+    let source = match save_path(args) {
+        Some(path) => path,
+        None => slots::list()
+            .into_iter()
+            .next()
+            .map(|slot| slot.path)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no saves to export"))?,
+    };
+    let gs = GameState::deserial(&source)?;
+
+    let text = match format_for(file, format) {
+        Format::Json => serde_json::to_string_pretty(&gs).map_err(invalid)?,
+        Format::Toml => toml::to_string(&gs).map_err(invalid)?,
+    };
+    fs::write(file, text)?;
+    Ok(source)
+}
+
+// read a JSON or TOML game, check it and store it as a save; returns the
+// save that was written
+pub fn import(args: &Args, file: &Path, format: Option<Format>, force: bool) -> Result<PathBuf> {
+    let text = fs::read_to_string(file)?;
+    let mut gs: GameState = match format_for(file, format) {
+        Format::Json => serde_json::from_str(&text).map_err(invalid)?,
+        Format::Toml => toml::from_str(&text).map_err(invalid)?,
+    };
+    gs.validate()?;
+    gs.untrusted = true;
+
+    let target = match save_path(args) {
+        Some(path) => path,
+        None => {
+            let name = file
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .filter(|name| {
+                    name.chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                })
+                .ok_or_else(|| invalid("pick a save with --slot or --save"))?;
+            slot_path(&name)
+        }
+    };
+
+    if target.exists() && !force {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} exists, use --force to replace it", target.display()),
+        ));
+    }
+    gs.save(&target)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+    use crate::mac;
+    use crate::tetlib::handle_input;
+    use crate::tetrominoe::Tetrominoe;
+    use crate::{HEIGHT, WIDTH};
+    use clap::Parser;
+    use serde_json::{json, Value};
+
+    // import a hand-edited JSON export into a save in the temp directory
+    fn import_edited(name: &str, edit: impl FnOnce(&mut Value)) -> Result<GameState> {
+        let dir = std::env::temp_dir().join(format!("tet-ris-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("game.json");
+        let save = dir.join("game.tetris");
+
+        let mut game = serde_json::to_value(GameState::seeded(WIDTH, HEIGHT, 5)).unwrap();
+        edit(&mut game);
+        fs::write(&file, game.to_string()).unwrap();
+
+        let args = Args::parse_from(["tet-ris", "--save", save.to_str().unwrap()]);
+        let result = import(&args, &file, None, true).and_then(|_| GameState::deserial(&save));
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    fn error(name: &str, edit: impl FnOnce(&mut Value)) -> String {
+        import_edited(name, edit).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn imports_a_game_as_untrusted() {
+        mac::use_test_key();
+        let gs = import_edited("plain", |_| ()).unwrap();
+        assert_eq!(gs.bag.seed(), 5);
+        assert!(gs.untrusted);
+    }
+
+    #[test]
+    fn checks_line_clears() {
+        mac::use_test_key();
+        let full = |game: &mut Value| {
+            for row in [18, 19] {
+                for cell in game["display"][row].as_array_mut().unwrap() {
+                    cell["game_state"] = json!("Landed");
+                    cell["color"] = json!("Cyan");
+                }
+            }
+        };
+
+        let gs = import_edited("clearing", |game| {
+            full(game);
+            game["line_clear"] = json!({ "rows": [19, 18], "tick": 1, "length": 3 });
+        })
+        .unwrap();
+        assert_eq!(gs.line_clear.unwrap().rows, [19, 18]);
+
+        let clear = |rows: Value, tick: usize, length: usize| {
+            move |game: &mut Value| {
+                full(game);
+                game["line_clear"] = json!({ "rows": rows, "tick": tick, "length": length });
+            }
+        };
+        assert_eq!(
+            error("no-ticks", clear(json!([19]), 0, 0)),
+            "line clear must have ticks left"
+        );
+        assert_eq!(
+            error("off-board", clear(json!([HEIGHT]), 0, 3)),
+            "cleared rows must be on the board"
+        );
+        assert_eq!(
+            error("twice", clear(json!([19, 19]), 0, 3)),
+            "cleared rows must be listed bottom first, once each"
+        );
+        assert_eq!(
+            error("locked", |game| game["locked"] = json!([[0, WIDTH]])),
+            "locked cells must be on the board"
+        );
+    }
+
+    #[test]
+    fn checks_tick_counters() {
+        mac::use_test_key();
+        for field in ["counter", "lock_timer", "lock_age"] {
+            assert_eq!(
+                error(field, |game| game[field] = json!(usize::MAX)),
+                "tick counters are out of range"
+            );
+        }
+    }
+
+    #[test]
+    fn checks_the_active_piece() {
+        mac::use_test_key();
+        // every way the piece can be turned, pushed against the left wall
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 5);
+        for turn in 0..4 {
+            handle_input(&mut gs, Action::Rotate);
+            for _ in 0..WIDTH {
+                handle_input(&mut gs, Action::Left);
+            }
+            let game = serde_json::to_value(&gs).unwrap();
+            import_edited(&format!("turn-{}", turn), |edited| *edited = game).unwrap();
+        }
+
+        assert_eq!(
+            error("row", |game| game["active_piece"]["row"] = json!(HEIGHT)),
+            "active piece must be on the board"
+        );
+        assert_eq!(
+            error("col", |game| {
+                game["active_piece"]["col"] = json!(usize::MAX);
+            }),
+            "active piece must be on the board"
+        );
+        assert_eq!(
+            error("rotation", |game| {
+                game["active_piece"]["rotation_state"] = json!(7);
+            }),
+            "active piece must be turned like its type"
+        );
+        assert_eq!(
+            error("type", |game| {
+                let other = ["I", "O"]
+                    .into_iter()
+                    .find(|&p| game["active_piece"]["ptype"] != p);
+                game["active_piece"]["ptype"] = json!(other);
+            }),
+            "active piece must be turned like its type"
+        );
+
+        // one block of the piece moved to the bottom left corner
+        let moved = |game: &mut Value| {
+            let (row, col) = (0..HEIGHT)
+                .flat_map(|row| (0..WIDTH).map(move |col| (row, col)))
+                .find(|&(row, col)| game["display"][row][col]["game_state"] == "Active")
+                .unwrap();
+            let block = game["display"][row][col].take();
+            game["display"][row][col] = serde_json::to_value(Tetrominoe::default()).unwrap();
+            game["display"][HEIGHT - 1][0] = block;
+        };
+        assert_eq!(
+            error("moved", moved),
+            "active cells must match the active piece"
+        );
+
+        let recolored = |game: &mut Value| {
+            let color = ["Red", "Cyan"]
+                .into_iter()
+                .find(|&c| game["active_piece"]["color"] != c);
+            for row in game["display"].as_array_mut().unwrap() {
+                for cell in row.as_array_mut().unwrap() {
+                    if cell["game_state"] == "Active" {
+                        cell["color"] = json!(color);
+                    }
+                }
+            }
+        };
+        assert_eq!(
+            error("recolored", recolored),
+            "active cells must match the active piece"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crossterm::style::Color;
+
+use crate::renderer::Renderer;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    fg: Color,
+    reverse: bool,
+}
+
+const BLANK: Cell = Cell {
+    ch: ' ',
+    fg: Color::Reset,
+    reverse: false,
+};
+
+// off-screen copy of a part of the terminal, one cell per column. Cells
+// nothing was drawn to stay None and are left alone on the terminal (the
+// walls, for one, are drawn separately), except that a cell drawn in the
+// previous frame but not this one is blanked.
+pub struct Frame {
+    width: u16,
+    height: u16,
+    cells: Vec<Option<Cell>>,
+    // what the terminal shows, as of the last flush
+    shown: Vec<Option<Cell>>,
+    // the terminal was cleared or drawn over, so `shown` can't be trusted
+    full: bool,
+}
+
+impl Frame {
+    pub const fn empty() -> Self {
+        Frame {
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            shown: Vec::new(),
+            full: true,
+        }
+    }
+
+    pub fn new(width: u16, height: u16) -> Self {
+        let mut frame = Frame::empty();
+        frame.resize(width, height);
+        frame
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        if (width, height) == self.size() {
+            return;
+        }
+        let len = width as usize * height as usize;
+        self.width = width;
+        self.height = height;
+        self.cells = vec![None; len];
+        self.shown = vec![None; len];
+        self.full = true;
+    }
+
+    // redraw every cell on the next flush
+    pub fn invalidate(&mut self) {
+        self.full = true;
+    }
+
+    // start composing the next frame
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+    }
+
+    pub fn print(&mut self, x: u16, y: u16, text: &str, fg: Color) {
+        self.print_styled(x, y, text, fg, false);
+    }
+
+    // text that runs past the right edge is cut off
+    pub fn print_styled(&mut self, x: u16, y: u16, text: &str, fg: Color, reverse: bool) {
+        if y >= self.height {
+            return;
+        }
+        for (x, ch) in (x..self.width).zip(text.chars()) {
+            self.cells[y as usize * self.width as usize + x as usize] =
+                Some(Cell { ch, fg, reverse });
+        }
+    }
+
+    // print the cells that changed since the last flush, as runs of
+    // neighbouring cells in the same style
+    pub fn flush(&mut self, out: &mut dyn Renderer) {
+        for y in 0..self.height {
+            let mut run: Option<(u16, String, Cell)> = None;
+            for x in 0..=self.width {
+                let changed = x < self.width && {
+                    let i = y as usize * self.width as usize + x as usize;
+                    if self.full {
+                        self.cells[i].is_some()
+                    } else {
+                        self.cells[i] != self.shown[i]
+                    }
+                };
+                let cell = changed.then(|| self.cell(x, y));
+
+                if let Some((start, text, style)) = &mut run {
+                    match cell {
+                        Some(cell) if (cell.fg, cell.reverse) == (style.fg, style.reverse) => {
+                            text.push(cell.ch);
+                            continue;
+                        }
+                        _ => out.print(*start, y, text, style.fg, style.reverse),
+                    }
+                }
+                run = cell.map(|cell| (x, cell.ch.to_string(), cell));
+            }
+        }
+        out.flush();
+
+        self.shown.clone_from(&self.cells);
+        self.full = false;
+    }
+
+    // what the terminal should show at (x, y); cells nothing was drawn to
+    // are blank
+    fn cell(&self, x: u16, y: u16) -> Cell {
+        self.cells[y as usize * self.width as usize + x as usize].unwrap_or(BLANK)
+    }
+}
@@ -5,6 +5,7 @@ use std::time::{Duration, Instant};
 pub struct GameScore {
     pub score: usize,
     pub level: usize,
+    pub lines: usize,
     pub elapsed_time: Duration,
     #[serde(skip)]
     last_update: Option<Instant>,
@@ -15,6 +16,7 @@ impl GameScore {
         GameScore {
             score: 0,
             level: 0,
+            lines: 0,
             elapsed_time: Duration::new(0, 0),
             last_update: Some(Instant::now()),
         }
@@ -1,32 +1,21 @@
 use crate::bag::Bag;
-use crate::tetlib::{get_input, new_piece, put_text};
+use crate::events::Events;
+use crate::keys::{self, Nav};
+use crate::paths::slot_path;
+use crate::savefile;
+use crate::stats::Stats;
+use crate::tetlib::{new_piece, prompt_text, put_text, read_key, EMP};
+use crate::tetrominoe::{State, TColor};
 use crate::{gamescore::GameScore, tetlib::init, tetrominoe::Tetrominoe};
 use crate::{HEIGHT, WIDTH};
-use bincode::{deserialize, serialize};
+use crossterm::event::KeyCode;
 use serde::{Deserialize, Serialize};
-use std::collections::hash_map::DefaultHasher;
-use std::fs::OpenOptions;
-use std::hash::Hash;
-use std::hash::Hasher;
-use std::io::{Read, Write};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
 use std::path::Path;
-use std::thread::sleep;
-use std::time::Duration;
 
-#[derive(Serialize, Deserialize)]
-struct GameWrapper {
-    game: GameState,
-    hash: u64,
-}
-
-impl GameWrapper {
-    fn verify(&self) -> bool {
-        let mut hasher = DefaultHasher::new();
-        self.game.hash(&mut hasher);
-        let hash = hasher.finish();
-        hash == self.hash
-    }
-}
+// far more than the tick counters reach in a game, and far from overflowing
+const MAX_TICKS: usize = u32::MAX as usize;
 
 #[derive(Serialize, Deserialize, Clone, Hash)]
 pub struct GameState {
@@ -36,111 +25,247 @@ pub struct GameState {
     pub hold_piece: Option<Tetrominoe>,
     pub next_piece: Tetrominoe,
     pub counter: usize,
+    // ticks the active piece has rested on the stack
+    pub lock_timer: usize,
+    // full rows waiting out the line clear delay
+    pub line_clear: Option<LineClear>,
+    // cells of the piece that locked last, and the ticks since it did
+    pub locked: Vec<(usize, usize)>,
+    pub lock_age: usize,
+    // for the HUD's pop-ups
+    #[serde(skip)]
+    pub events: Events,
     pub is_game_over: bool,
     pub bag: Bag,
+    pub stats: Stats,
+    // loaded from an unsigned save or imported from a text file, so its
+    // score can't be vouched for
+    pub untrusted: bool,
+}
+
+// rows that are full but still on the board; the game stands still until
+// `tick` reaches `length`, so the animation takes the same ticks in play and
+// in replays
+#[derive(Serialize, Deserialize, Clone, Hash)]
+pub struct LineClear {
+    // bottom row first
+    pub rows: Vec<usize>,
+    pub tick: usize,
+    pub length: usize,
 }
 
 impl GameState {
     pub fn new(width: usize, height: usize) -> Self {
+        GameState::seeded(width, height, Bag::new().seed())
+    }
+
+    pub fn seeded(width: usize, height: usize, seed: u64) -> Self {
+        let mut bag = Bag::seeded(seed);
         let mut gs = GameState {
             display: init(width, height),
             active_piece: Tetrominoe::new(None, None),
             gamescore: GameScore::new(),
             hold_piece: None,
-            next_piece: Tetrominoe::random(&mut Bag::new()),
+            next_piece: Tetrominoe::random(&mut bag),
             counter: 0,
+            lock_timer: 0,
+            line_clear: None,
+            locked: Vec::new(),
+            lock_age: 0,
+            events: Events::default(),
             is_game_over: false,
-            bag: Bag::new(),
+            bag,
+            stats: Stats::default(),
+            untrusted: false,
         };
-        init(width, height);
         new_piece(&mut gs, None);
         gs
     }
 
     // return true if user wants to repeat
-    pub fn serial(&mut self) -> bool {
+    pub fn serial(&mut self, path: Option<&Path>) -> Result<bool> {
         // repeat
         if confirmation("New game?") {
-            return true;
+            return Ok(true);
         }
 
         // saving
         if !confirmation("Save game?") {
-            return false;
+            return Ok(false);
         }
 
-        let path = String::from("save.tetris");
+        self.save_prompt(path)?;
+        Ok(false)
+    }
 
-        if Path::new(&path).exists() && !confirmation("Overwrite save?") {
-            return false;
+    // save to `path`, or to a slot named by the player if there is no path
+    // yet; return false if the player backed out
+    pub fn save_prompt(&mut self, path: Option<&Path>) -> Result<bool> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match prompt_text(WIDTH as u16, HEIGHT as u16, "Name") {
+                Some(name) => slot_path(&name),
+                None => return Ok(false),
+            },
+        };
+
+        if path.exists() && !confirmation("Overwrite save?") {
+            return Ok(false);
         }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&path)
-            .unwrap();
+        self.save(&path)?;
+        Ok(true)
+    }
 
+    pub fn save(&mut self, path: &Path) -> Result<()> {
         self.gamescore.stop_timer();
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
-        let hash = hasher.finish();
+        let data = savefile::encode(self)?;
 
-        let game_wrapper = GameWrapper {
-            game: self.clone(),
-            hash,
-        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)
+    }
+
+    // sanity checks for games that didn't come from this program, e.g. a
+    // hand-edited export
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: &str| Err(Error::new(ErrorKind::InvalidData, msg.to_string()));
+        let is_piece = |p: &Tetrominoe| "IJLOSTZ".contains(p.ptype);
 
-        if !game_wrapper.verify() {
-            println!("Hash verification failed. Aborting save.");
-            return false;
+        if self.display.len() != HEIGHT || self.display.iter().any(|row| row.len() != WIDTH) {
+            return invalid(&format!("board must be {}x{}", WIDTH, HEIGHT));
         }
 
-        let serialized_data = serialize(&game_wrapper).expect("Failed to serialize game.");
-        file.write_all(&serialized_data).unwrap();
-        false
-    }
+        let mut active = 0;
+        for cell in self.display.iter().flatten() {
+            match cell.game_state {
+                State::Active | State::Landed if cell.color == TColor::Empty => {
+                    return invalid("filled cell without a color");
+                }
+                State::Active => active += 1,
+                _ => (),
+            }
+        }
+        if active > 4 || (active != 4 && !self.is_game_over) {
+            return invalid("board must hold exactly one active piece");
+        }
 
-    pub fn deserial(path: String, width: usize, height: usize) -> Self {
-        if !Path::new(&path).exists() {
-            return GameState::new(width, height);
+        if !is_piece(&self.active_piece)
+            || !is_piece(&self.next_piece)
+            || self.hold_piece.as_ref().is_some_and(|p| !is_piece(p))
+        {
+            return invalid("unknown active, next or held piece");
+        }
+        if self.next_piece != Tetrominoe::from(self.next_piece.ptype, None) {
+            return invalid("next piece must be upright");
         }
 
-        let mut file = OpenOptions::new().read(true).open(path).unwrap();
+        let piece = &self.active_piece;
+        if piece.row >= HEIGHT || piece.col >= WIDTH {
+            return invalid("active piece must be on the board");
+        }
+        // T comes back from a full turn higher than it spawned
+        let mut turned = Tetrominoe::from(piece.ptype, None);
+        turned.set_pos(piece.row, piece.col);
+        if turned != *piece
+            && !(0..4).any(|_| {
+                turned.rotate();
+                turned == *piece
+            })
+        {
+            return invalid("active piece must be turned like its type");
+        }
 
-        let mut serialized_data = Vec::new();
-        file.read_to_end(&mut serialized_data).unwrap();
+        // the board keeps the piece's blocks; its position can lag behind
+        // them, so only the shape and color have to match
+        if active == 4 {
+            let cells = self
+                .display
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter(|(_, cell)| cell.game_state == State::Active)
+                .map(|(i, cell)| (i / WIDTH, i % WIDTH, cell.color));
+            let blocks = piece
+                .shape
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter(|(_, &c)| c != EMP)
+                .map(|(i, _)| (i / 4, i % 4, piece.color));
+            if normalized(cells) != normalized(blocks) {
+                return invalid("active cells must match the active piece");
+            }
+        }
 
-        let game_wrapper: GameWrapper =
-            deserialize(&serialized_data).expect("Failed to deserialize game.");
+        if [self.counter, self.lock_timer, self.lock_age]
+            .iter()
+            .any(|&ticks| ticks > MAX_TICKS)
+        {
+            return invalid("tick counters are out of range");
+        }
 
-        if !game_wrapper.verify() {
-            println!("Save file is corrupted. Starting new game.");
-            sleep(Duration::from_secs(2));
-            return GameState::new(width, height);
+        if let Some(clear) = &self.line_clear {
+            if clear.length == 0 || clear.tick >= clear.length {
+                return invalid("line clear must have ticks left");
+            }
+            if clear.rows.is_empty() || clear.rows.iter().any(|&row| row >= HEIGHT) {
+                return invalid("cleared rows must be on the board");
+            }
+            // clear_lines removes them one by one, so they have to be in order
+            if clear.rows.windows(2).any(|rows| rows[0] <= rows[1]) {
+                return invalid("cleared rows must be listed bottom first, once each");
+            }
+        }
+        if self
+            .locked
+            .iter()
+            .any(|&(row, col)| row >= HEIGHT || col >= WIDTH)
+        {
+            return invalid("locked cells must be on the board");
         }
 
-        let mut game = game_wrapper.game;
+        if !self.bag.is_valid() {
+            return invalid("bag may only hold one of each piece");
+        }
+        if self.gamescore.level != self.gamescore.score / 1000 {
+            return invalid("level doesn't match score");
+        }
+        Ok(())
+    }
+
+    pub fn deserial(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        let mut game = savefile::decode(&data)?;
         game.gamescore.reset_timer();
-        init(width, height);
-        game
+        Ok(game)
     }
 }
 
-fn confirmation(prompt: &str) -> bool {
+// cells moved to the top left corner
+fn normalized(cells: impl Iterator<Item = (usize, usize, TColor)>) -> Vec<(usize, usize, TColor)> {
+    let cells: Vec<_> = cells.collect();
+    let top = cells.iter().map(|&(row, _, _)| row).min().unwrap_or(0);
+    let left = cells.iter().map(|&(_, col, _)| col).min().unwrap_or(0);
+    cells
+        .into_iter()
+        .map(|(row, col, color)| (row - top, col - left, color))
+        .collect()
+}
+
+pub fn confirmation(prompt: &str) -> bool {
     loop {
         put_text(
             WIDTH.try_into().unwrap(),
             HEIGHT.try_into().unwrap(),
             format!("{} (y/n)", prompt).as_str(),
         );
-        loop {
-            let key = get_input();
-            match key {
-                'y' => return true,
-                'n' => return false,
-                _ => continue,
-            }
+        match read_key() {
+            Some(KeyCode::Char('y')) => return true,
+            Some(KeyCode::Char('n')) | None => return false,
+            Some(code) if keys::nav(code) == Some(Nav::Back) => return false,
+            _ => continue,
         }
     }
 }
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use crate::args::Args;
+use crate::input::{Action, Input};
+
+// terminals send a held key as a stream of presses; one that arrives
+// within this long of the last is taken as the key still being held
+const REPEAT_GAP: Duration = Duration::from_millis(100);
+
+struct Held {
+    action: Action,
+    pressed: Instant,
+    last_seen: Instant,
+    last_move: Instant,
+}
+
+// delayed auto shift and auto repeat rate for left and right, applied to
+// the actions coming from the terminal before they reach the game (and its
+// replay). Repeats can only be dropped, so a held key moves no faster than
+// the terminal repeats it
+pub struct Handling {
+    das: Duration,
+    arr: Duration,
+    held: Option<Held>,
+}
+
+impl Handling {
+    pub fn from_args(args: &Args) -> Self {
+        Handling {
+            das: Duration::from_millis(args.das),
+            arr: Duration::from_millis(args.arr),
+            held: None,
+        }
+    }
+
+    // the actions to pass on; repeats of a held move that come too early
+    // are dropped
+    pub fn filter(&mut self, inputs: Vec<Input>) -> Vec<Action> {
+        inputs
+            .into_iter()
+            .filter(|input| self.accept(input))
+            .map(|input| input.action)
+            .collect()
+    }
+
+    fn accept(&mut self, input: &Input) -> bool {
+        let Input { action, at: now } = *input;
+        if action != Action::Left && action != Action::Right {
+            self.held = None;
+            return true;
+        }
+
+        match &mut self.held {
+            Some(held) if held.action == action && now - held.last_seen < REPEAT_GAP => {
+                held.last_seen = now;
+                if now - held.pressed < self.das || now - held.last_move < self.arr {
+                    return false;
+                }
+                held.last_move = now;
+                true
+            }
+            _ => {
+                self.held = Some(Held {
+                    action,
+                    pressed: now,
+                    last_seen: now,
+                    last_move: now,
+                });
+                true
+            }
+        }
+    }
+}
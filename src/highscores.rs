@@ -0,0 +1,334 @@
+use bincode::{deserialize, serialize};
+use chrono::{Local, TimeZone};
+use crossterm::{event::KeyCode, style::Color};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::gamestate::GameState;
+use crate::mac;
+use crate::paths::scores_path;
+use crate::renderer;
+use crate::rules::Mode;
+use crate::tetlib::{prompt_text, put_text, read_key};
+use crate::{HEIGHT, WIDTH};
+
+// High scores are kept in one file per mode, sealed (see mac.rs) under the
+// magic "TTHS", with a bincode encoded Vec<Entry>, best first, as the payload
+const MAGIC: &[u8; 4] = b"TTHS";
+const VERSION: u16 = 1;
+
+const TOP: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    // seconds played
+    pub time: u64,
+    // unix timestamp of the game over
+    pub date: i64,
+    pub seed: u64,
+}
+
+impl Entry {
+    fn new(name: String, gs: &GameState) -> Self {
+        Entry {
+            name,
+            score: gs.gamescore.score,
+            lines: gs.gamescore.lines,
+            level: gs.gamescore.level,
+            time: gs.gamescore.elapsed_time.as_secs(),
+            date: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            seed: gs.bag.seed(),
+        }
+    }
+
+    fn time(&self) -> String {
+        format!("{}:{:02}", self.time / 60, self.time % 60)
+    }
+
+    fn date(&self) -> String {
+        Local
+            .timestamp_opt(self.date, 0)
+            .single()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+}
+
+pub fn load(mode: Mode) -> Result<Vec<Entry>> {
+    let data = match fs::read(scores_path(mode)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    decode(&data)
+}
+
+fn save(mode: Mode, entries: &[Entry]) -> Result<()> {
+    let path = scores_path(mode);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, encode(entries)?)
+}
+
+fn encode(entries: &[Entry]) -> Result<Vec<u8>> {
+    let payload = serialize(entries).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    mac::seal(MAGIC, VERSION, &payload)
+}
+
+fn decode(data: &[u8]) -> Result<Vec<Entry>> {
+    match mac::open(MAGIC, data)? {
+        (VERSION, payload) => {
+            deserialize(payload).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        }
+        (version, _) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported high score version {}", version),
+        )),
+    }
+}
+
+fn qualifies(entries: &[Entry], score: usize) -> bool {
+    score > 0 && (entries.len() < TOP || entries.iter().any(|e| score > e.score))
+}
+
+// games from untrusted saves or imports never make the table
+fn eligible(entries: &[Entry], gs: &GameState) -> bool {
+    !gs.untrusted && qualifies(entries, gs.gamescore.score)
+}
+
+// add an entry behind any equal scores; returns its rank (0 based)
+fn insert(entries: &mut Vec<Entry>, entry: Entry) -> usize {
+    let rank = entries
+        .iter()
+        .position(|e| entry.score > e.score)
+        .unwrap_or(entries.len());
+    entries.insert(rank, entry);
+    entries.truncate(TOP);
+    rank
+}
+
+// after game over: ask for a name if the score made the table; returns
+// the rank it was entered at (0 based)
+pub fn record(mode: Mode, gs: &GameState) -> Option<usize> {
+    let mut entries = match load(mode) {
+        Ok(entries) => entries,
+        Err(_) => {
+            put_text(WIDTH as u16, HEIGHT as u16, "Scores unreadable");
+            sleep(Duration::from_secs(2));
+            return None;
+        }
+    };
+
+    if !eligible(&entries, gs) {
+        return None;
+    }
+
+    put_text(WIDTH as u16, HEIGHT as u16, "New high score!");
+    sleep(Duration::from_secs(1));
+    let name = prompt_text(WIDTH as u16, HEIGHT as u16, "Name")?;
+    let rank = insert(&mut entries, Entry::new(name, gs));
+    if save(mode, &entries).is_err() {
+        put_text(WIDTH as u16, HEIGHT as u16, "Failed to save score");
+        sleep(Duration::from_secs(2));
+        return None;
+    }
+    Some(rank)
+}
+
+// show the table until a key is pressed
+pub fn show(mode: Mode, highlight: Option<usize>) {
+    let entries = load(mode).unwrap_or_default();
+    draw(mode, &entries, highlight);
+    while read_key() == Some(KeyCode::Null) {
+        draw(mode, &entries, highlight);
+    }
+}
+
+fn draw(mode: Mode, entries: &[Entry], highlight: Option<usize>) {
+    renderer::with(|out| {
+        out.clear();
+        out.print(
+            2,
+            1,
+            &format!("H I G H  S C O R E S  ({})", mode.name()),
+            Color::Reset,
+            false,
+        );
+        out.print(
+            2,
+            3,
+            &format!(
+                "{:>3}  {:<12} {:>8} {:>5} {:>5} {:>7}  {}",
+                "#", "Name", "Score", "Lines", "Level", "Time", "Date"
+            ),
+            Color::Reset,
+            false,
+        );
+
+        for (row, (i, entry)) in (4..).zip(entries.iter().enumerate()) {
+            out.print(
+                2,
+                row,
+                &format!(
+                    "{:>3}  {:<12} {:>8} {:>5} {:>5} {:>7}  {}",
+                    i + 1,
+                    entry.name,
+                    entry.score,
+                    entry.lines,
+                    entry.level,
+                    entry.time(),
+                    entry.date()
+                ),
+                Color::Reset,
+                Some(i) == highlight,
+            );
+        }
+        if entries.is_empty() {
+            out.print(2, 4, "No scores yet", Color::Reset, false);
+        }
+
+        out.print(2, TOP as u16 + 5, "Press any key", Color::Reset, false);
+        out.flush();
+    });
+}
+
+// print the tables for `tet-ris scores`
+pub fn print(modes: &[Mode]) -> Result<()> {
+    for (i, &mode) in modes.iter().enumerate() {
+        let entries = load(mode)?;
+        if i > 0 {
+            println!();
+        }
+        println!("{}", mode.name());
+        if entries.is_empty() {
+            println!("  no scores yet");
+            continue;
+        }
+        println!(
+            "{:>3}  {:<12} {:>8} {:>5} {:>5} {:>7}  {:<10}  Seed",
+            "#", "Name", "Score", "Lines", "Level", "Time", "Date"
+        );
+        for (i, entry) in entries.iter().enumerate() {
+            println!(
+                "{:>3}  {:<12} {:>8} {:>5} {:>5} {:>7}  {:<10}  {:016x}",
+                i + 1,
+                entry.name,
+                entry.score,
+                entry.lines,
+                entry.level,
+                entry.time(),
+                entry.date(),
+                entry.seed
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: usize) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            lines: score / 100,
+            level: score / 1000,
+            time: 90,
+            date: 0,
+            seed: 7,
+        }
+    }
+
+    fn names(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_the_top_ten() {
+        let mut entries = Vec::new();
+        for score in 1..=TOP {
+            insert(&mut entries, entry(&score.to_string(), score * 100));
+        }
+        assert_eq!(entries.len(), TOP);
+        assert_eq!(entries[0].score, 1000);
+        assert_eq!(entries[TOP - 1].score, 100);
+
+        // a better score pushes the last one out
+        assert_eq!(insert(&mut entries, entry("new", 550)), 5);
+        assert_eq!(entries.len(), TOP);
+        assert_eq!(entries[5].name, "new");
+        assert_eq!(entries[TOP - 1].score, 200);
+
+        // ties go behind the scores already there
+        assert_eq!(insert(&mut entries, entry("tie", 1000)), 1);
+        assert_eq!(names(&entries[..3]), ["10", "tie", "9"]);
+    }
+
+    #[test]
+    fn qualifies_for_a_place() {
+        let mut entries = Vec::new();
+        assert!(!qualifies(&entries, 0));
+        assert!(qualifies(&entries, 1));
+
+        for _ in 0..TOP {
+            insert(&mut entries, entry("full", 500));
+        }
+        assert!(!qualifies(&entries, 400));
+        assert!(!qualifies(&entries, 500));
+        assert!(qualifies(&entries, 501));
+        assert_eq!(insert(&mut entries, entry("best", 501)), 0);
+    }
+
+    #[test]
+    fn skips_untrusted_games() {
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        gs.gamescore.score = 300;
+        assert!(eligible(&[], &gs));
+
+        gs.untrusted = true;
+        assert!(!eligible(&[], &gs));
+    }
+
+    #[test]
+    fn round_trip() {
+        mac::use_test_key();
+        let entries = vec![entry("a", 300), entry("b", 200)];
+        let loaded = decode(&encode(&entries).unwrap()).unwrap();
+        assert_eq!(names(&loaded), ["a", "b"]);
+        assert_eq!(loaded[1].score, 200);
+        assert_eq!(loaded[1].seed, 7);
+    }
+
+    #[test]
+    fn rejects_a_tampered_file() {
+        mac::use_test_key();
+        let mut data = encode(&[entry("a", 300)]).unwrap();
+
+        // raise the score and fix up the checksum
+        let score = mac::HEADER_LEN + 8 + 8 + 1;
+        data[score] ^= 0x80;
+        let checksum = crc32fast::hash(&data[mac::HEADER_LEN..]);
+        data[6..10].copy_from_slice(&checksum.to_le_bytes());
+        assert!(decode(&data)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("modified outside tet-ris"));
+    }
+}
@@ -0,0 +1,343 @@
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Error, ErrorKind, Result, Write},
+    path::Path,
+};
+
+use crate::args::HistoryFormat;
+use crate::gamestate::GameState;
+use crate::paths::history_path;
+use crate::rules::{Mode, Rules};
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const CHART_WIDTH: usize = 60;
+
+// one finished game, stored as a line of JSON in history.jsonl
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    // RFC 3339, local time
+    pub date: String,
+    pub mode: Mode,
+    pub seed: u64,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    // seconds played
+    pub time: u64,
+    pub pps: f64,
+    #[serde(flatten)]
+    pub rules: Rules,
+    pub tick_ms: u64,
+}
+
+impl Record {
+    pub fn new(gs: &GameState, mode: Mode, rules: Rules, tick_ms: u64) -> Self {
+        Record {
+            date: Local::now().to_rfc3339(),
+            mode,
+            seed: gs.bag.seed(),
+            score: gs.gamescore.score,
+            lines: gs.gamescore.lines,
+            level: gs.gamescore.level,
+            time: gs.gamescore.elapsed_time.as_secs(),
+            pps: gs.stats.pps(gs.gamescore.elapsed_time),
+            rules,
+            tick_ms,
+        }
+    }
+
+    fn day(&self) -> Option<NaiveDate> {
+        DateTime::parse_from_rfc3339(&self.date)
+            .ok()
+            .map(|date| date.date_naive())
+    }
+
+    fn ruleset(&self) -> String {
+        let mut rules = vec![
+            if self.rules.hold { "hold" } else { "no-hold" }.to_string(),
+            if self.rules.ghost {
+                "ghost"
+            } else {
+                "no-ghost"
+            }
+            .to_string(),
+            format!("{}ms", self.tick_ms),
+        ];
+        if self.rules.lock_delay > 0 {
+            rules.push(format!(
+                "lock {}ms",
+                self.rules.lock_delay as u64 * self.tick_ms
+            ));
+        }
+        if self.rules.line_clear_delay > 0 {
+            rules.push(format!(
+                "clear {}ms",
+                self.rules.line_clear_delay as u64 * self.tick_ms
+            ));
+        }
+        if self.rules.soft_drop > 1 {
+            rules.push(format!("soft-drop x{}", self.rules.soft_drop));
+        }
+        rules.join(" ")
+    }
+}
+
+pub fn append(record: &Record) -> Result<()> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(record).map_err(Error::other)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+// all games, oldest first; lines that can't be read (e.g. cut short by a
+// crash) are skipped
+pub fn load() -> Result<Vec<Record>> {
+    let file = match fs::File::open(history_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str(&line?) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+pub fn filter(
+    records: Vec<Record>,
+    mode: Option<Mode>,
+    since: Option<NaiveDate>,
+    last: Option<usize>,
+) -> Vec<Record> {
+    let mut records: Vec<Record> = records
+        .into_iter()
+        .filter(|r| mode.is_none_or(|mode| r.mode == mode))
+        .filter(|r| since.is_none_or(|since| r.day().is_some_and(|day| day >= since)))
+        .collect();
+
+    if let Some(last) = last {
+        records.drain(..records.len().saturating_sub(last));
+    }
+    records
+}
+
+fn format_for(file: &Path, format: Option<HistoryFormat>) -> HistoryFormat {
+    format.unwrap_or_else(|| match file.extension() {
+        Some(ext) if ext == "json" => HistoryFormat::Json,
+        _ => HistoryFormat::Csv,
+    })
+}
+
+pub fn export(records: &[Record], file: &Path, format: Option<HistoryFormat>) -> Result<()> {
+    let text = match format_for(file, format) {
+        HistoryFormat::Json => serde_json::to_string_pretty(records).map_err(Error::other)?,
+        HistoryFormat::Csv => {
+            let mut text = String::from(
+                "date,mode,seed,score,lines,level,time,pps,hold,ghost,lock_delay,soft_drop,line_clear_delay,tick_ms\n",
+            );
+            for r in records {
+                text.push_str(&format!(
+                    "{},{},{:016x},{},{},{},{},{:.3},{},{},{},{},{},{}\n",
+                    r.date,
+                    r.mode.name(),
+                    r.seed,
+                    r.score,
+                    r.lines,
+                    r.level,
+                    r.time,
+                    r.pps,
+                    r.rules.hold,
+                    r.rules.ghost,
+                    r.rules.lock_delay,
+                    r.rules.soft_drop,
+                    r.rules.line_clear_delay,
+                    r.tick_ms
+                ));
+            }
+            text
+        }
+    };
+    fs::write(file, text)
+}
+
+// print the games as a table, followed by charts of score and PPS
+pub fn print(records: &[Record]) {
+    if records.is_empty() {
+        println!("no games yet");
+        return;
+    }
+
+    println!(
+        "{:<16}  {:<8} {:>8} {:>5} {:>5} {:>7} {:>5}  Rules",
+        "Date", "Mode", "Score", "Lines", "Level", "Time", "PPS"
+    );
+    for r in records {
+        let date = DateTime::parse_from_rfc3339(&r.date)
+            .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!(
+            "{:<16}  {:<8} {:>8} {:>5} {:>5} {:>4}:{:02} {:>5.2}  {}",
+            date,
+            r.mode.name(),
+            r.score,
+            r.lines,
+            r.level,
+            r.time / 60,
+            r.time % 60,
+            r.pps,
+            r.ruleset()
+        );
+    }
+
+    let scores: Vec<f64> = records.iter().map(|r| r.score as f64).collect();
+    let pps: Vec<f64> = records.iter().map(|r| r.pps).collect();
+    println!();
+    println!("Score {}", chart(&scores, 0));
+    println!("PPS   {}", chart(&pps, 2));
+}
+
+// sparkline followed by its range; long histories are averaged down to
+// CHART_WIDTH columns
+fn chart(values: &[f64], precision: usize) -> String {
+    let chunk = values.len().div_ceil(CHART_WIDTH).max(1);
+    let points: Vec<f64> = values
+        .chunks(chunk)
+        .map(|c| c.iter().sum::<f64>() / c.len() as f64)
+        .collect();
+
+    let min = points.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = points.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let line: String = points
+        .iter()
+        .map(|&v| {
+            if max > min {
+                BARS[((v - min) / (max - min) * (BARS.len() - 1) as f64).round() as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect();
+
+    format!("{}  {:.*} .. {:.*}", line, precision, min, precision, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, mode: Mode, score: usize) -> Record {
+        Record {
+            date: date.to_string(),
+            mode,
+            seed: 0xabc,
+            score,
+            lines: score / 100,
+            level: score / 1000,
+            time: 75,
+            pps: 1.23456,
+            rules: Rules {
+                hold: true,
+                ghost: false,
+                lock_delay: 5,
+                soft_drop: 2,
+                line_clear_delay: 0,
+            },
+            tick_ms: 100,
+        }
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            record("2024-03-01T20:00:00+01:00", Mode::Marathon, 100),
+            record("2024-03-02T09:30:00+01:00", Mode::Sirtet, 200),
+            record("2024-03-03T23:59:59+01:00", Mode::Marathon, 300),
+            record("not a date", Mode::Marathon, 400),
+            record("2024-03-05T00:00:00+01:00", Mode::Marathon, 500),
+        ]
+    }
+
+    fn scores(records: &[Record]) -> Vec<usize> {
+        records.iter().map(|r| r.score).collect()
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(filter(records(), None, None, None).len(), 5);
+        assert_eq!(
+            scores(&filter(records(), Some(Mode::Sirtet), None, None)),
+            [200]
+        );
+        assert_eq!(
+            scores(&filter(records(), Some(Mode::Marathon), None, Some(2))),
+            [400, 500]
+        );
+        assert_eq!(
+            scores(&filter(records(), None, None, Some(10))),
+            [100, 200, 300, 400, 500]
+        );
+
+        // by the day the game was played, and never for unreadable dates
+        let since = NaiveDate::from_ymd_opt(2024, 3, 2);
+        assert_eq!(
+            scores(&filter(records(), None, since, None)),
+            [200, 300, 500]
+        );
+        assert_eq!(
+            scores(&filter(records(), Some(Mode::Marathon), since, Some(1))),
+            [500]
+        );
+    }
+
+    fn exported(name: &str, format: Option<HistoryFormat>) -> String {
+        let path = std::env::temp_dir().join(format!("tet-ris-{}-{}", std::process::id(), name));
+        export(&records()[..2], &path, format).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        text
+    }
+
+    #[test]
+    fn exports_csv() {
+        let text = exported("history.csv", None);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("date,mode,seed,score,"));
+        assert_eq!(
+            lines[1],
+            "2024-03-01T20:00:00+01:00,marathon,0000000000000abc,100,1,0,75,1.235,true,false,5,2,0,100"
+        );
+        assert!(lines[2].starts_with("2024-03-02T09:30:00+01:00,sirtet,"));
+
+        // the format given wins over the extension
+        assert_eq!(exported("history.json", Some(HistoryFormat::Csv)), text);
+    }
+
+    #[test]
+    fn exports_json() {
+        let text = exported("history.json", None);
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 2);
+        assert_eq!(value[0]["mode"], "marathon");
+        assert_eq!(value[0]["score"], 100);
+        assert_eq!(value[0]["lock_delay"], 5);
+        assert_eq!(value[1]["date"], "2024-03-02T09:30:00+01:00");
+
+        // and it reads back
+        let read: Vec<Record> = serde_json::from_str(&text).unwrap();
+        assert_eq!(scores(&read), [100, 200]);
+    }
+}
@@ -0,0 +1,81 @@
+use crossterm::event::{self, poll, Event, KeyCode, KeyEvent, KeyEventKind};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::keys;
+use crate::layout;
+use crate::signals;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Right,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    Pause,
+    Quit,
+    // replays only
+    FastForward,
+}
+
+impl Action {
+    // changes the game state; everything else (pause, quit, ...) is not
+    // needed to reproduce a game
+    pub fn is_engine(self) -> bool {
+        matches!(
+            self,
+            Action::Left
+                | Action::Right
+                | Action::Rotate
+                | Action::SoftDrop
+                | Action::HardDrop
+                | Action::Hold
+        )
+    }
+}
+
+// an action and when its key was read
+#[derive(Clone, Copy)]
+pub struct Input {
+    pub action: Action,
+    pub at: Instant,
+}
+
+// every action pressed since the last call, oldest first; only Quit once a
+// signal arrived, so the caller can save and exit. An unbound 'f' fast
+// forwards during playback
+pub fn poll_actions(playback: bool) -> Vec<Input> {
+    if signals::received().is_some() {
+        return vec![Input {
+            action: Action::Quit,
+            at: Instant::now(),
+        }];
+    }
+    let mut inputs = Vec::new();
+    while poll(Duration::from_millis(0)).unwrap() {
+        let code = match event::read().unwrap() {
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) => code,
+            Event::Resize(..) => {
+                layout::resized();
+                continue;
+            }
+            _ => continue,
+        };
+        let action = match keys::lookup(code) {
+            Some(action) => action,
+            None if playback && code == KeyCode::Char('f') => Action::FastForward,
+            None => continue,
+        };
+        inputs.push(Input {
+            action,
+            at: Instant::now(),
+        });
+    }
+    inputs
+}
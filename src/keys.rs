@@ -0,0 +1,389 @@
+use clap::ValueEnum;
+use crossterm::{event::KeyCode, style::Color};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind, Result},
+    sync::{OnceLock, RwLock},
+};
+
+use crate::config;
+use crate::input::Action;
+use crate::renderer;
+use crate::tetlib::read_key;
+
+// bindable actions: config name, action and label
+pub const ACTIONS: [(&str, Action, &str); 8] = [
+    ("left", Action::Left, "Move left"),
+    ("right", Action::Right, "Move right"),
+    ("rotate", Action::Rotate, "Rotate"),
+    ("soft-drop", Action::SoftDrop, "Soft drop"),
+    ("hard-drop", Action::HardDrop, "Hard drop"),
+    ("hold", Action::Hold, "Hold"),
+    ("pause", Action::Pause, "Pause"),
+    ("quit", Action::Quit, "Quit"),
+];
+
+// [bindings] table of the config file: action name to key names
+pub type Bindings = BTreeMap<String, Vec<String>>;
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Default,
+    Vim,
+    Wasd,
+    Guideline,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Keymap {
+    // keys for each entry of ACTIONS
+    pub keys: [Vec<KeyCode>; 8],
+}
+
+static KEYMAP: OnceLock<RwLock<Keymap>> = OnceLock::new();
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Self {
+        use KeyCode::*;
+        let keys = match preset {
+            Preset::Default => [
+                vec![Left],
+                vec![Right],
+                vec![Up],
+                vec![Down],
+                vec![Char(' ')],
+                vec![Char('c')],
+                vec![Char('p')],
+                vec![Char('q')],
+            ],
+            Preset::Vim => [
+                vec![Char('h'), Left],
+                vec![Char('l'), Right],
+                vec![Char('k'), Up],
+                vec![Char('j'), Down],
+                vec![Char(' ')],
+                vec![Char('c')],
+                vec![Char('p')],
+                vec![Char('q')],
+            ],
+            Preset::Wasd => [
+                vec![Char('a'), Left],
+                vec![Char('d'), Right],
+                vec![Char('w'), Up],
+                vec![Char('s'), Down],
+                vec![Char(' ')],
+                vec![Char('c'), Char('e')],
+                vec![Char('p'), Esc],
+                vec![Char('q')],
+            ],
+            // there is only clockwise rotation, so z is left unbound
+            Preset::Guideline => [
+                vec![Left],
+                vec![Right],
+                vec![Up, Char('x')],
+                vec![Down],
+                vec![Char(' ')],
+                vec![Char('c')],
+                vec![Esc, F(1)],
+                vec![Char('q')],
+            ],
+        };
+        Keymap { keys }
+    }
+
+    // a preset with the actions from the config file replaced
+    pub fn new(preset: Preset, bindings: &Bindings) -> Result<Self> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+
+        let mut keymap = Keymap::preset(preset);
+        for (action, names) in bindings {
+            let i = ACTIONS
+                .iter()
+                .position(|(name, _, _)| name == action)
+                .ok_or_else(|| invalid(format!("unknown action {}", action)))?;
+            keymap.keys[i] = names
+                .iter()
+                .map(|name| parse_key(name).ok_or_else(|| invalid(format!("unknown key {}", name))))
+                .collect::<Result<_>>()?;
+        }
+        keymap.check()?;
+        Ok(keymap)
+    }
+
+    // without a pause and a quit key there is no way out of a game
+    fn check(&self) -> Result<()> {
+        for (i, (name, action, _)) in ACTIONS.iter().enumerate() {
+            if matches!(action, Action::Pause | Action::Quit) && self.keys[i].is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} needs a key", name),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // action bound to `code`
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.keys
+            .iter()
+            .position(|keys| keys.contains(&code))
+            .map(|i| ACTIONS[i].1)
+    }
+
+    // bind `code` to action `i` only, replacing or adding to its keys
+    pub fn bind(&mut self, i: usize, code: KeyCode, add: bool) {
+        for keys in self.keys.iter_mut() {
+            keys.retain(|&k| k != code);
+        }
+        if !add {
+            self.keys[i].clear();
+        }
+        self.keys[i].push(code);
+    }
+
+    // the actions that differ from `preset`, as a [bindings] table
+    pub fn bindings(&self, preset: Preset) -> Bindings {
+        let base = Keymap::preset(preset);
+        ACTIONS
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.keys[i] != base.keys[i])
+            .map(|(i, (name, _, _))| {
+                (
+                    name.to_string(),
+                    self.keys[i].iter().map(|&k| key_name(k)).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn set(keymap: Keymap) {
+    let lock = KEYMAP.get_or_init(|| RwLock::new(keymap.clone()));
+    *lock.write().unwrap() = keymap;
+}
+
+pub fn current() -> Keymap {
+    match KEYMAP.get() {
+        Some(lock) => lock.read().unwrap().clone(),
+        None => Keymap::preset(Preset::Default),
+    }
+}
+
+// action bound to `code` in the current keymap
+pub fn lookup(code: KeyCode) -> Option<Action> {
+    match KEYMAP.get() {
+        Some(lock) => lock.read().unwrap().action(code),
+        None => Keymap::preset(Preset::Default).action(code),
+    }
+}
+
+// moves in menus
+#[derive(Clone, Copy, PartialEq)]
+pub enum Nav {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+
+// menu move for `code`: the arrows, Enter and Esc, or the keys bound to
+// rotate, soft drop, hard drop, pause and quit
+pub fn nav(code: KeyCode) -> Option<Nav> {
+    match code {
+        KeyCode::Up => Some(Nav::Up),
+        KeyCode::Down => Some(Nav::Down),
+        KeyCode::Enter => Some(Nav::Select),
+        KeyCode::Esc => Some(Nav::Back),
+        code => match lookup(code)? {
+            Action::Rotate => Some(Nav::Up),
+            Action::SoftDrop => Some(Nav::Down),
+            Action::HardDrop => Some(Nav::Select),
+            Action::Pause | Action::Quit => Some(Nav::Back),
+            _ => None,
+        },
+    }
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match name.to_lowercase().as_str() {
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        code => format!("{:?}", code).to_lowercase(),
+    }
+}
+
+// in-game rebinding; changes are written to the config file on the way out
+pub fn screen(preset: Preset) {
+    let mut keymap = current();
+    let mut selected = 0;
+    let mut status = String::new();
+    loop {
+        draw(&keymap, selected, &status);
+        let Some(code) = read_key() else {
+            break;
+        };
+        let add = code == KeyCode::Tab;
+        match (code, nav(code)) {
+            (KeyCode::Backspace, _) => {
+                keymap.keys[selected] = Keymap::preset(preset).keys[selected].clone();
+            }
+            (KeyCode::Tab, _) | (_, Some(Nav::Select)) => {
+                status = format!("Press a key for {}", ACTIONS[selected].2);
+                draw(&keymap, selected, &status);
+                let key = loop {
+                    match read_key() {
+                        Some(KeyCode::Null) => draw(&keymap, selected, &status),
+                        key => break key,
+                    }
+                };
+                let Some(key) = key else {
+                    break;
+                };
+                if parse_key(&key_name(key)) != Some(key) {
+                    status = format!("{} can't be bound", key_name(key));
+                    continue;
+                }
+                let mut bound = keymap.clone();
+                bound.bind(selected, key, add);
+                status = match bound.check() {
+                    Ok(()) => {
+                        keymap = bound;
+                        String::new()
+                    }
+                    Err(e) => e.to_string(),
+                };
+            }
+            (_, Some(Nav::Up)) => selected = selected.saturating_sub(1),
+            (_, Some(Nav::Down)) => selected = (selected + 1).min(ACTIONS.len() - 1),
+            (_, Some(Nav::Back)) => break,
+            _ => (),
+        }
+    }
+
+    if keymap != current() {
+        if let Err(e) = config::save_bindings(&keymap.bindings(preset)) {
+            draw(
+                &keymap,
+                selected,
+                &format!("Failed to save bindings: {}", e),
+            );
+            read_key();
+        }
+        set(keymap);
+    }
+}
+
+fn draw(keymap: &Keymap, selected: usize, status: &str) {
+    renderer::with(|out| {
+        out.clear();
+        out.print(2, 1, "C O N T R O L S", Color::Reset, false);
+
+        for (row, (i, (_, _, label))) in (3..).zip(ACTIONS.iter().enumerate()) {
+            let keys: Vec<String> = keymap.keys[i].iter().map(|&k| key_name(k)).collect();
+            out.print(
+                2,
+                row,
+                &format!(
+                    "{} {:<12} {:<30}",
+                    if i == selected { ">" } else { " " },
+                    label,
+                    keys.join(", ")
+                ),
+                Color::Reset,
+                i == selected,
+            );
+        }
+
+        let bottom = ACTIONS.len() as u16 + 4;
+        out.print(
+            2,
+            bottom,
+            "Enter: set key  Tab: add key  Backspace: reset  Esc: back",
+            Color::Reset,
+            false,
+        );
+        out.print(2, bottom + 2, status, Color::Reset, false);
+        out.flush();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(action: &str, keys: &[&str]) -> Bindings {
+        let keys = keys.iter().map(|key| key.to_string()).collect();
+        Bindings::from([(action.to_string(), keys)])
+    }
+
+    #[test]
+    fn applies_bindings_over_a_preset() {
+        let keymap = Keymap::new(Preset::Vim, &bindings("quit", &["x", "esc"])).unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('x')), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Char('q')), None);
+        assert_eq!(keymap.action(KeyCode::Char('k')), Some(Action::Rotate));
+        assert_eq!(
+            keymap.bindings(Preset::Vim),
+            bindings("quit", &["x", "esc"])
+        );
+    }
+
+    #[test]
+    fn needs_a_pause_and_a_quit_key() {
+        let error = |action| {
+            Keymap::new(Preset::Default, &bindings(action, &[]))
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(error("quit").as_deref(), Some("quit needs a key"));
+        assert_eq!(error("pause").as_deref(), Some("pause needs a key"));
+        assert_eq!(error("hold"), None);
+    }
+}
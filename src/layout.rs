@@ -0,0 +1,124 @@
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, poll, Event, KeyEvent, KeyEventKind},
+    style::Print,
+    terminal::{self, Clear, ClearType},
+    QueueableCommand,
+};
+use std::{
+    io::{stdout, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+use crate::input::Action;
+use crate::keys;
+use crate::signals;
+use crate::{HEIGHT, WIDTH};
+
+// where the game screen (hold panel, board, HUD and statistics) sits in
+// the terminal; everything on it is drawn relative to `left` and `top`
+struct Layout {
+    width: u16,
+    height: u16,
+    left: u16,
+    top: u16,
+}
+
+static LAYOUT: RwLock<Layout> = RwLock::new(Layout {
+    width: 0,
+    height: 0,
+    left: 0,
+    top: 0,
+});
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+// size the game screen for the statistics panel and the replay status line
+pub fn set_size(stats: bool, replay: bool) {
+    {
+        let mut layout = LAYOUT.write().unwrap();
+        layout.width = WIDTH as u16 * 4 + if stats { 31 } else { 14 };
+        layout.height = HEIGHT as u16 + if replay { 5 } else { 3 };
+    }
+    fit();
+}
+
+// centre the game screen; false if the terminal is too small for it
+pub fn fit() -> bool {
+    let mut layout = LAYOUT.write().unwrap();
+    let (cols, rows) = terminal::size().unwrap_or((layout.width, layout.height));
+    layout.left = cols.saturating_sub(layout.width) / 2;
+    layout.top = rows.saturating_sub(layout.height) / 2;
+    cols >= layout.width && rows >= layout.height
+}
+
+// width and height of the game screen
+pub fn size() -> (u16, u16) {
+    let layout = LAYOUT.read().unwrap();
+    (layout.width, layout.height)
+}
+
+// cursor position on the game screen
+pub fn at(x: u16, y: u16) -> MoveTo {
+    let layout = LAYOUT.read().unwrap();
+    MoveTo(layout.left + x, layout.top + y)
+}
+
+// called by the input layer when the terminal reports a new size
+pub fn resized() {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+// true once after every resize
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+// refit after a resize, showing a message and waiting while the terminal is
+// too small; false if the player quit meanwhile. The caller redraws the screen
+// afterwards (and handles any signal that ends the wait early)
+pub fn wait_for_room() -> bool {
+    let mut stdout = stdout();
+    while !fit() {
+        let (cols, rows) = terminal::size().unwrap_or_default();
+        let (width, height) = size();
+        stdout
+            .queue(Clear(ClearType::All))
+            .unwrap()
+            .queue(MoveTo(0, 0))
+            .unwrap()
+            .queue(Print(format!(
+                "Terminal too small: {}x{}, need {}x{}",
+                cols, rows, width, height
+            )))
+            .unwrap();
+        stdout.flush().unwrap();
+
+        loop {
+            if signals::received().is_some() {
+                return true;
+            }
+            if !poll(Duration::from_millis(100)).unwrap() {
+                continue;
+            }
+            match event::read().unwrap() {
+                Event::Resize(..) => break,
+                Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if keys::lookup(code) == Some(Action::Quit) => {
+                    stdout.queue(Clear(ClearType::All)).unwrap();
+                    return false;
+                }
+                _ => (),
+            }
+        }
+    }
+    RESIZED.store(false, Ordering::Relaxed);
+    true
+}
@@ -0,0 +1,181 @@
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, RngCore};
+use sha2::Sha256;
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result, Write},
+    sync::OnceLock,
+};
+
+use crate::paths::key_path;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Saves, replays and high scores are sealed the same way:
+//
+//   magic    4 bytes   kind of file
+//   version  u16 LE    format version of the payload
+//   checksum u32 LE    CRC32 of the payload
+//   mac      32 bytes  HMAC-SHA256 of version and payload
+//   payload
+//
+// The checksum tells a damaged file apart from one that was edited or
+// signed by another installation.
+const LEN: usize = 32;
+pub const HEADER_LEN: usize = 10 + LEN;
+const KEY_LEN: usize = 32;
+
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+// per-installation secret, created on first use
+fn key() -> Result<&'static [u8]> {
+    if let Some(key) = KEY.get() {
+        return Ok(key);
+    }
+
+    let path = key_path();
+    let key = match fs::read(&path) {
+        Ok(key) if key.len() == KEY_LEN => key,
+        Ok(_) => return Err(Error::new(ErrorKind::InvalidData, "key file is damaged")),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let mut key = vec![0; KEY_LEN];
+            thread_rng().fill_bytes(&mut key);
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(&path)?.write_all(&key)?;
+            key
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(KEY.get_or_init(|| key))
+}
+
+// sign with a fixed key rather than the installation's; tests call this
+// before anything is signed
+#[cfg(test)]
+pub fn use_test_key() {
+    assert_eq!(KEY.get_or_init(|| vec![0; KEY_LEN]), &vec![0; KEY_LEN]);
+}
+
+fn hmac(parts: &[&[u8]]) -> Result<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(key()?).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    Ok(mac)
+}
+
+fn sign(parts: &[&[u8]]) -> Result<[u8; LEN]> {
+    Ok(hmac(parts)?.finalize().into_bytes().into())
+}
+
+fn verify(parts: &[&[u8]], tag: &[u8]) -> Result<bool> {
+    Ok(hmac(parts)?.verify_slice(tag).is_ok())
+}
+
+pub fn seal(magic: &[u8; 4], version: u16, payload: &[u8]) -> Result<Vec<u8>> {
+    let version = version.to_le_bytes();
+
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(magic);
+    data.extend_from_slice(&version);
+    data.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    data.extend_from_slice(&sign(&[&version, payload])?);
+    data.extend_from_slice(payload);
+    Ok(data)
+}
+
+// check a file written by `seal`; returns its version and payload
+pub fn open<'a>(magic: &[u8; 4], data: &'a [u8]) -> Result<(u16, &'a [u8])> {
+    let invalid = |msg: &str| Err(Error::new(ErrorKind::InvalidData, msg.to_string()));
+
+    if !data.starts_with(magic) {
+        return invalid("unknown file format");
+    }
+    if data.len() < HEADER_LEN {
+        return invalid("file is truncated");
+    }
+    let (header, payload) = data.split_at(HEADER_LEN);
+    let version = &header[4..6];
+    let checksum = u32::from_le_bytes([header[6], header[7], header[8], header[9]]);
+
+    if crc32fast::hash(payload) != checksum {
+        return invalid("file is corrupted");
+    }
+    if !verify(&[version, payload], &header[10..])? {
+        return invalid("file was modified outside tet-ris or signed by another installation");
+    }
+    Ok((u16::from_le_bytes([version[0], version[1]]), payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGIC: &[u8; 4] = b"TEST";
+
+    fn sealed() -> Vec<u8> {
+        use_test_key();
+        seal(MAGIC, 3, b"payload").unwrap()
+    }
+
+    fn error(data: &[u8]) -> String {
+        open(MAGIC, data).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = sealed();
+        assert_eq!(data.len(), HEADER_LEN + 7);
+        assert_eq!(open(MAGIC, &data).unwrap(), (3, &b"payload"[..]));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(error(b"TES"), "unknown file format");
+        assert_eq!(error(&sealed()[..HEADER_LEN - 1]), "file is truncated");
+
+        let mut data = sealed();
+        data[..4].copy_from_slice(b"OTHR");
+        assert_eq!(error(&data), "unknown file format");
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut data = sealed();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert_eq!(error(&data), "file is corrupted");
+
+        let mut data = sealed();
+        data[6] ^= 1;
+        assert_eq!(error(&data), "file is corrupted");
+    }
+
+    #[test]
+    fn rejects_a_bad_mac() {
+        // a consistent checksum doesn't help without the key
+        let mut data = sealed();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let checksum = crc32fast::hash(&data[HEADER_LEN..]);
+        data[6..10].copy_from_slice(&checksum.to_le_bytes());
+        assert!(error(&data).contains("modified outside tet-ris"));
+
+        // the version is signed too
+        let mut data = sealed();
+        data[4] ^= 1;
+        assert!(error(&data).contains("modified outside tet-ris"));
+
+        let mut data = sealed();
+        data[HEADER_LEN - 1] ^= 1;
+        assert!(error(&data).contains("modified outside tet-ris"));
+    }
+}
@@ -2,146 +2,459 @@
 
 mod args;
 mod bag;
+mod config;
+mod events;
+mod export;
+mod frame;
 mod gamescore;
 mod gamestate;
+mod handling;
+mod highscores;
+mod history;
+mod input;
+mod keys;
+mod layout;
+mod mac;
+mod paths;
+mod playback;
+mod popup;
+mod recovery;
+mod renderer;
+mod replay;
+mod rules;
+mod savefile;
+mod signals;
+mod slots;
+mod stats;
+mod summary;
 mod tetlib;
 mod tetrominoe;
+mod theme;
 
 use std::{
-    io::{stdout, Write},
+    fs::File,
+    io::{self, stdout, BufWriter, Write},
+    panic,
+    path::{Path, PathBuf},
+    process::exit,
     thread::sleep,
     time::Duration,
 };
 
-use crossterm::{
-    cursor::Show,
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-
-use clap::Parser;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
 
-use gamestate::GameState;
+use gamestate::{confirmation, GameState};
+use handling::Handling;
+use input::Action;
+use popup::Popup;
+use recovery::Autosave;
+use replay::Replay;
+use rules::{Mode, Rules};
 use tetlib::*;
 
 pub const WIDTH: usize = 10;
 pub const HEIGHT: usize = 20;
 
+// what to do once a game loop ends
+enum GameEnd {
+    // 'q': ask about a new game and saving
+    Ask,
+    // show the results screen
+    GameOver,
+    Restart,
+    Quit,
+}
+
+// for errors in the config file, which name it
+fn config_error(e: io::Error) -> ! {
+    eprintln!(
+        "Failed to read config {}: {}",
+        paths::config_path().display(),
+        e
+    );
+    exit(1);
+}
+
 fn main() {
-    let mut args = args::Args::parse();
+    let matches = args::Args::command().get_matches();
+    let mut args = args::Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let config = config::load()
+        .and_then(|config| config.apply(&mut args, &matches).map(|_| config))
+        .unwrap_or_else(|e| config_error(e));
+    keys::set(keys::Keymap::new(args.keys, &config.bindings).unwrap_or_else(|e| config_error(e)));
+
+    // the original look and --no-colors leave the terminal's colours alone
+    let name = if args.og || args.no_colors {
+        "monochrome"
+    } else {
+        &args.theme
+    };
+    let mut theme = theme::Theme::new(name, &config.themes).unwrap_or_else(|e| {
+        // themes from the config file can only be broken there; a name that
+        // isn't one of them is wrong wherever it was given
+        match matches.value_source("theme") {
+            _ if config.themes.contains_key(name) => config_error(e),
+            Some(ValueSource::CommandLine) => eprintln!("Invalid --theme: {}", e),
+            Some(ValueSource::EnvVariable) => eprintln!("Invalid TET_RIS_THEME: {}", e),
+            _ => config_error(e),
+        }
+        exit(1);
+    });
+    theme.glyphs = args.glyphs;
+    let mode = args.color_mode.detect();
+    // crossterm checks NO_COLOR on its own; a --color-mode given over it wins
+    crossterm::style::force_color_output(mode != theme::ColorMode::None);
+    theme::set(theme.fit(mode));
+
+    // `panic = "abort"` skips unwinding, so put the terminal back before
+    // the panic message is printed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
 
     if args.og {
         args.no_colors = true;
         args.chars = "[]".to_string();
     }
 
-    const MAX_LEVEL: usize = 20;
-    const GRAV_TICK: usize = 40;
-    const LEVEL_MULT: f64 = 0.85;
+    match &args.command {
+        Some(args::Command::Replay { file, frames }) => {
+            watch_replay(file, frames.as_deref(), &args);
+            return;
+        }
+        Some(args::Command::Save { action }) => {
+            transfer_save(action, &args);
+            return;
+        }
+        Some(args::Command::History {
+            mode,
+            since,
+            last,
+            export,
+            format,
+        }) => {
+            let result = history::load().and_then(|records| {
+                let records = history::filter(records, *mode, *since, *last);
+                match export {
+                    Some(file) => history::export(&records, Path::new(file), *format)
+                        .map(|()| println!("Exported {} games to {}", records.len(), file)),
+                    None => {
+                        history::print(&records);
+                        Ok(())
+                    }
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Failed to read history: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        Some(args::Command::Scores { mode }) => {
+            let modes = mode.map_or(Mode::ALL.to_vec(), |mode| vec![mode]);
+            if let Err(e) = highscores::print(&modes) {
+                eprintln!("Failed to read high scores: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        None => (),
+    }
+
+    let rules = Rules::from_args(&args);
+    let mode = Mode::from_args(&args);
+    let mut handling = Handling::from_args(&args);
+
+    let mut save_path = paths::save_path(&args);
 
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen).unwrap();
-    enable_raw_mode().unwrap();
-    let mut gs = if let Some(path) = &args.save {
-        if path_exists(path) {
-            GameState::deserial(path.clone(), WIDTH, HEIGHT)
-        } else {
-            GameState::new(WIDTH, HEIGHT)
+    setup_terminal();
+    signals::register();
+    // menus before the game are centred like it
+    layout::set_size(args.stats, false);
+
+    let recovered = recovery::interrupted();
+    let recovered = match recovered {
+        Some(gs) if confirmation("Recover last game?") => Some(gs),
+        _ => {
+            // killed while asking: keep the game for the next launch
+            signals::check();
+            recovery::clear();
+            None
         }
-    } else {
-        GameState::new(WIDTH, HEIGHT)
     };
 
-    if gs.is_game_over {
-        gs.gamescore.stop_timer();
+    if recovered.is_none() && save_path.is_none() {
+        save_path = continue_unfinished();
     }
 
-    if args.sirtet {
-        sirtet_borders(WIDTH, HEIGHT);
+    if recovered.is_none() && save_path.is_none() {
+        match slots::manager() {
+            slots::Choice::Load(path) => save_path = Some(path),
+            slots::Choice::New => (),
+            slots::Choice::Quit => {
+                signals::check();
+                restore_terminal();
+                return;
+            }
+        }
+    }
+
+    // the save the running game was loaded from, if any
+    let mut loaded = None;
+    let mut gs = match (recovered, &save_path) {
+        (Some(gs), _) => gs,
+        (None, Some(path)) if path.exists() => match GameState::deserial(path) {
+            Ok(gs) => {
+                loaded = Some(path.clone());
+                gs
+            }
+            Err(e) => {
+                restore_terminal();
+                eprintln!("Failed to load save {}: {}", path.display(), e);
+                exit(1);
+            }
+        },
+        _ => GameState::new(WIDTH, HEIGHT),
+    };
+
+    if gs.is_game_over {
+        gs.gamescore.stop_timer();
     }
 
     // loop for new game
     loop {
+        let mut replay = Replay::new(&gs, rules, args.gravity);
+        let mut autosave = Autosave::new();
+        let mut end = GameEnd::Ask;
+        let mut popup = Popup::default();
+        let mut refit = true;
+
         // game loop
         loop {
-            let prev_display = gs.display.clone();
+            let mut resumed = false;
+
+            // new game or terminal size: centre the game, pausing while it
+            // doesn't fit
+            if refit || layout::take_resized() {
+                if !layout::wait_for_room() {
+                    break;
+                }
+                draw_walls(args.sirtet);
+                refit = false;
+                resumed = true;
+            }
 
             // handle input
-            let key = get_input();
+            let actions = handling.filter(input::poll_actions(false));
 
-            // quit
-            if key == 'q' {
-                break;
+            // killed or hung up: keep the game in the recovery file. Menus and
+            // waits return early when this happens, so it is caught here
+            if let Some(signal) = signals::received() {
+                if let Err(e) = autosave.save(&gs) {
+                    restore_terminal();
+                    eprintln!("Failed to autosave game: {}", e);
+                }
+                signals::exit(signal);
             }
 
-            if key == 'p' {
-                let mut key = get_input();
-                put_text(WIDTH as u16, HEIGHT as u16, "P A U S E D");
-                stdout.flush().unwrap();
-                while key != 'p' && key != 'q' {
-                    key = get_input();
-                    sleep(Duration::from_millis(10));
-                }
+            // quit
+            if actions.contains(&Action::Quit) {
+                break;
             }
 
-            // gravity
-            if gs.counter
-                >= (GRAV_TICK as f64 * LEVEL_MULT.powf(gs.gamescore.level as f64)) as usize
-            {
-                if gravity(&mut gs) {
-                    gs.is_game_over = true;
-                    break;
+            if actions.contains(&Action::Pause) {
+                let choice = menu(
+                    WIDTH as u16,
+                    HEIGHT as u16,
+                    "P A U S E D",
+                    &["Resume", "Save & Quit", "Restart", "Controls", "Quit"],
+                );
+                match choice {
+                    Some(1) => match gs.save_prompt(save_path.as_deref()) {
+                        Ok(true) => {
+                            end = GameEnd::Quit;
+                            break;
+                        }
+                        Ok(false) => (),
+                        Err(e) => {
+                            let autosaved = autosave.save(&gs);
+                            restore_terminal();
+                            eprintln!("Failed to save game: {}", e);
+                            if let Err(e) = autosaved {
+                                eprintln!("Failed to autosave game: {}", e);
+                            }
+                            exit(1);
+                        }
+                    },
+                    Some(2) => {
+                        end = GameEnd::Restart;
+                        break;
+                    }
+                    Some(3) => {
+                        keys::screen(args.keys);
+                        draw_walls(args.sirtet);
+                    }
+                    Some(4) => {
+                        end = GameEnd::Quit;
+                        break;
+                    }
+                    _ => (),
                 }
-                gs.counter = if gs.gamescore.level < MAX_LEVEL {
-                    0
-                } else {
-                    100
-                };
+                resumed = true;
             }
 
-            // handle input
-            handle_input(&mut gs, key);
-
-            // hold piece
-            if key == 'c' && !args.hold {
-                hold(&mut gs);
+            // advance game
+            replay.record(&actions);
+            if tick(&mut gs, &actions, &rules) {
+                end = GameEnd::GameOver;
+                break;
             }
-
-            // full line
-            full_line(&mut gs);
-
-            // ghost piece
-            if !args.ghost {
-                ghost_piece(&mut gs);
+            if autosave.update(&gs).is_err() {
+                put_text(WIDTH as u16, HEIGHT as u16, "Autosave failed");
+                sleep(Duration::from_secs(2));
+                resumed = true;
             }
+            popup.update(&mut gs);
 
-            // check if gs.display was changed
-            let is_updated = gs.display != prev_display || gs.is_game_over;
-
-            // render
-            render(
-                &mut gs,
-                is_updated,
-                &args.chars,
-                &args.no_colors,
-                &args.sirtet,
-            );
+            // render; only what changed is drawn, so this is cheap when idle
+            render(&mut gs, resumed, &args, &popup);
             sleep(Duration::from_millis(args.gravity));
             stdout.flush().unwrap();
-            gs.counter += 1;
         }
 
-        // put_text(WIDTH as u16, HEIGHT as u16, "G A M E  O V E R");
-        if !gs.serial() {
+        // keep the game recoverable until the player decides what to do with it
+        if autosave.save(&gs).is_err() {
+            put_text(WIDTH as u16, HEIGHT as u16, "Autosave failed");
+            sleep(Duration::from_secs(2));
+        }
+        replay.finish(&gs);
+        if replay.save_new().is_err() {
+            put_text(WIDTH as u16, HEIGHT as u16, "Failed to save replay");
+            sleep(Duration::from_secs(2));
+        }
+
+        let quit = match end {
+            GameEnd::Restart => false,
+            GameEnd::Quit => true,
+            GameEnd::GameOver => {
+                // mark the save finished, so it isn't offered to continue
+                if let Some(path) = loaded.take() {
+                    if gs.save(&path).is_err() {
+                        put_text(WIDTH as u16, HEIGHT as u16, "Failed to update save");
+                        sleep(Duration::from_secs(2));
+                    }
+                }
+                let record = history::Record::new(&gs, mode, rules, args.gravity);
+                if history::append(&record).is_err() {
+                    put_text(WIDTH as u16, HEIGHT as u16, "Failed to save history");
+                    sleep(Duration::from_secs(2));
+                }
+                let rank = highscores::record(mode, &gs);
+                matches!(
+                    summary::show(&gs, mode, rank, &replay, &args),
+                    summary::Choice::Quit
+                )
+            }
+            GameEnd::Ask => match gs.serial(save_path.as_deref()) {
+                Ok(repeat) => !repeat,
+                Err(e) => {
+                    restore_terminal();
+                    eprintln!("Failed to save game: {}", e);
+                    exit(1);
+                }
+            },
+        };
+        // killed on the way out: the game is still in the recovery file
+        signals::check();
+        recovery::clear();
+
+        if quit {
             break;
         }
         gs = GameState::new(WIDTH, HEIGHT);
+        loaded = None;
     }
-    disable_raw_mode().unwrap();
-    execute!(stdout, LeaveAlternateScreen).unwrap();
-    execute!(stdout, Show).unwrap();
+    restore_terminal();
 }
 
-fn path_exists(path: &String) -> bool {
-    std::path::Path::new(path).exists()
+fn transfer_save(action: &args::SaveCommand, args: &args::Args) {
+    let result = match action {
+        args::SaveCommand::Export { file, format } => {
+            export::export(args, Path::new(file), *format)
+                .map(|save| println!("Exported {} to {}", save.display(), file))
+        }
+        args::SaveCommand::Import {
+            file,
+            format,
+            force,
+        } => export::import(args, Path::new(file), *format, *force)
+            .map(|save| println!("Imported {} into {}", file, save.display())),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn watch_replay(file: &str, frames: Option<&str>, args: &args::Args) {
+    let replay = match Replay::load(Path::new(file)) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Failed to load replay {}: {}", file, e);
+            exit(1);
+        }
+    };
+
+    layout::set_size(args.stats, true);
+    let in_sync = match frames {
+        Some(path) => {
+            let out: Box<dyn Write + Send> = if path == "-" {
+                Box::new(stdout())
+            } else {
+                match File::create(path) {
+                    Ok(file) => Box::new(BufWriter::new(file)),
+                    Err(e) => {
+                        eprintln!("Failed to create {}: {}", path, e);
+                        exit(1);
+                    }
+                }
+            };
+            let (width, height) = layout::size();
+            renderer::set(Box::new(renderer::Text::new(out, width, height)));
+            playback::frames(&replay, args)
+        }
+        None => {
+            setup_terminal();
+            signals::register();
+            let in_sync = playback::play(&replay, args);
+            signals::check();
+            restore_terminal();
+            in_sync
+        }
+    };
+
+    if !in_sync {
+        eprintln!(
+            "Replay desynced: expected a final score of {}",
+            replay.final_score
+        );
+        exit(1);
+    }
+}
+
+// offer to continue the most recent unfinished save
+fn continue_unfinished() -> Option<PathBuf> {
+    let slot = slots::list()
+        .into_iter()
+        .find(|slot| slot.game.as_ref().is_some_and(|gs| !gs.is_game_over))?;
+
+    if confirmation(&format!("Continue {}?", slot.name)) {
+        Some(slot.path)
+    } else {
+        None
+    }
 }
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use crate::args::Args;
+use crate::rules::Mode;
+
+// platform data directory, e.g. ~/.local/share/tet-ris on Linux
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tet-ris")
+}
+
+// platform config directory, e.g. ~/.config/tet-ris on Linux
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tet-ris")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+// secret used to sign saves and replays
+pub fn key_path() -> PathBuf {
+    config_dir().join("key")
+}
+
+pub fn replay_dir() -> PathBuf {
+    data_dir().join("replays")
+}
+
+pub fn replay_path(name: &str) -> PathBuf {
+    replay_dir().join(name).with_extension("tetris-replay")
+}
+
+pub fn scores_path(mode: Mode) -> PathBuf {
+    data_dir()
+        .join("scores")
+        .join(mode.name())
+        .with_extension("tetris-scores")
+}
+
+// every finished game, one JSON object per line
+pub fn history_path() -> PathBuf {
+    data_dir().join("history.jsonl")
+}
+
+pub fn recovery_path() -> PathBuf {
+    data_dir().join("recovery.tetris")
+}
+
+pub fn slot_dir() -> PathBuf {
+    data_dir().join("saves")
+}
+
+pub fn slot_path(name: &str) -> PathBuf {
+    slot_dir().join(name).with_extension("tetris")
+}
+
+// the one save file used for loading, saving and overwrite checks; None
+// until the player picks a slot
+pub fn save_path(args: &Args) -> Option<PathBuf> {
+    match (&args.save, &args.slot) {
+        (Some(path), _) => Some(Path::new(path).to_path_buf()),
+        (None, Some(name)) => Some(slot_path(name)),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,362 @@
+use crossterm::style::Color;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::args::Args;
+use crate::gamestate::GameState;
+use crate::input::{poll_actions, Action};
+use crate::layout;
+use crate::popup::Popup;
+use crate::renderer;
+use crate::replay::Replay;
+use crate::tetlib::{draw_walls, put_text, render, tick};
+use crate::{HEIGHT, WIDTH};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Speed {
+    Normal,
+    Double,
+    Quadruple,
+    Max,
+}
+
+impl Speed {
+    fn next(self) -> Self {
+        match self {
+            Speed::Normal => Speed::Double,
+            Speed::Double => Speed::Quadruple,
+            Speed::Quadruple => Speed::Max,
+            Speed::Max => Speed::Normal,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Speed::Normal => "1x",
+            Speed::Double => "2x",
+            Speed::Quadruple => "4x",
+            Speed::Max => "max",
+        }
+    }
+}
+
+// state right after a piece was drawn, so seeking doesn't have to
+// re-simulate the whole replay
+struct Checkpoint {
+    tick: usize,
+    cursor: usize,
+    gs: GameState,
+}
+
+struct Playback<'a> {
+    replay: &'a Replay,
+    gs: GameState,
+    tick: usize,
+    cursor: usize,
+    checkpoints: Vec<Checkpoint>,
+    final_score: usize,
+    popup: Popup,
+}
+
+impl<'a> Playback<'a> {
+    fn new(replay: &'a Replay) -> Self {
+        let mut pb = Playback {
+            replay,
+            gs: replay.start.clone(),
+            tick: 0,
+            cursor: 0,
+            checkpoints: Vec::new(),
+            final_score: replay.start.gamescore.score,
+            popup: Popup::default(),
+        };
+        pb.checkpoint();
+
+        // simulate once up front to find where each piece starts
+        while !pb.is_finished() {
+            let prev_bag = pb.gs.bag.clone();
+            pb.step();
+            // every new piece is drawn from the bag
+            if pb.gs.bag != prev_bag {
+                pb.checkpoint();
+            }
+        }
+        pb.final_score = pb.gs.gamescore.score;
+
+        pb.seek(0);
+        pb
+    }
+
+    fn checkpoint(&mut self) {
+        self.checkpoints.push(Checkpoint {
+            tick: self.tick,
+            cursor: self.cursor,
+            gs: self.gs.clone(),
+        });
+    }
+
+    fn is_finished(&self) -> bool {
+        self.tick >= self.replay.ticks || self.gs.is_game_over
+    }
+
+    fn step(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        let actions = self.replay.input_at(self.tick, &mut self.cursor);
+        tick(&mut self.gs, &actions, &self.replay.rules);
+        self.popup.update(&mut self.gs);
+        self.tick += 1;
+    }
+
+    // index of the piece currently in play
+    fn piece(&self) -> usize {
+        self.checkpoints
+            .iter()
+            .rposition(|c| c.tick <= self.tick)
+            .unwrap_or(0)
+    }
+
+    fn seek(&mut self, piece: usize) {
+        let checkpoint = &self.checkpoints[piece.min(self.checkpoints.len() - 1)];
+        self.tick = checkpoint.tick;
+        self.cursor = checkpoint.cursor;
+        self.gs = checkpoint.gs.clone();
+        self.popup = Popup::default();
+    }
+
+    fn status(&self, paused: bool, speed: Speed) -> String {
+        format!(
+            "{} {:<4} piece {}/{}  tick {}/{}",
+            if paused { "||" } else { ">>" },
+            speed.label(),
+            self.piece() + 1,
+            self.checkpoints.len(),
+            self.tick,
+            self.replay.ticks,
+        )
+    }
+
+    // shown over the board once the replay is over
+    fn result(&self) -> String {
+        if self.final_score == self.replay.final_score {
+            "Replay OK".to_string()
+        } else {
+            format!("DESYNC {}/{}", self.final_score, self.replay.final_score)
+        }
+    }
+
+    fn update_time(&mut self) {
+        let elapsed = Duration::from_millis(self.tick as u64 * self.replay.tick_ms);
+        self.gs.gamescore.elapsed_time = self.replay.start.gamescore.elapsed_time + elapsed;
+        self.gs.gamescore.stop_timer();
+    }
+}
+
+// watch a replay; return true if it ended on the score it recorded
+pub fn play(replay: &Replay, args: &Args) -> bool {
+    // animations are timed in the replay's ticks
+    let args = &Args {
+        gravity: replay.tick_ms,
+        ..args.clone()
+    };
+    let mut pb = Playback::new(replay);
+    let mut paused = false;
+    let mut speed = Speed::Normal;
+    let mut reported = false;
+    let mut status_shown = String::new();
+    let frame = Duration::from_millis(replay.tick_ms.max(1));
+    let mut refit = true;
+
+    loop {
+        let prev_tick = pb.tick;
+        let actions: Vec<Action> = poll_actions(true)
+            .iter()
+            .map(|input| input.action)
+            .collect();
+        if actions.contains(&Action::Quit) {
+            break;
+        }
+
+        // redraw everything, including the end-of-replay message
+        if refit || layout::take_resized() {
+            if !layout::wait_for_room() {
+                break;
+            }
+            draw_walls(args.sirtet);
+            refit = false;
+            reported = false;
+            status_shown.clear();
+        }
+        for action in actions {
+            match action {
+                Action::Pause => paused = !paused,
+                Action::FastForward => speed = speed.next(),
+                Action::SoftDrop if paused => pb.step(),
+                Action::Left => pb.seek(pb.piece().saturating_sub(1)),
+                Action::Right => pb.seek(pb.piece() + 1),
+                _ => (),
+            }
+        }
+
+        if !paused {
+            match speed {
+                Speed::Normal => pb.step(),
+                Speed::Double => (0..2).for_each(|_| pb.step()),
+                Speed::Quadruple => (0..4).for_each(|_| pb.step()),
+                Speed::Max => {
+                    let start = Instant::now();
+                    while !pb.is_finished() && start.elapsed() < frame {
+                        pb.step();
+                    }
+                }
+            }
+        }
+
+        // seeking back clears the end-of-replay message
+        let cleared = reported && pb.tick < prev_tick;
+        if cleared {
+            reported = false;
+        }
+
+        // status line below the board, drawn with the next frame
+        let status = pb.status(paused, speed);
+        if status != status_shown {
+            show_status(&status);
+            status_shown = status;
+        }
+
+        pb.update_time();
+        render(&mut pb.gs, cleared, args, &pb.popup);
+
+        if pb.is_finished() && !reported {
+            put_text(WIDTH as u16, HEIGHT as u16, &pb.result());
+            reported = true;
+        }
+
+        sleep(frame);
+    }
+
+    pb.final_score == replay.final_score
+}
+
+// play the whole replay without waiting or reading keys, drawing every tick
+// with the current renderer; return true if it ended on the score it recorded
+pub fn frames(replay: &Replay, args: &Args) -> bool {
+    // animations are timed in the replay's ticks
+    let args = &Args {
+        gravity: replay.tick_ms,
+        ..args.clone()
+    };
+    let mut pb = Playback::new(replay);
+    draw_walls(args.sirtet);
+
+    loop {
+        show_status(&pb.status(false, Speed::Normal));
+        pb.update_time();
+        render(&mut pb.gs, false, args, &pb.popup);
+        if pb.is_finished() {
+            break;
+        }
+        pb.step();
+    }
+    put_text(WIDTH as u16, HEIGHT as u16, &pb.result());
+
+    pb.final_score == replay.final_score
+}
+
+fn show_status(status: &str) {
+    renderer::with(|out| {
+        out.print(
+            11,
+            HEIGHT as u16 + 4,
+            &format!("{:<48}", status),
+            Color::Reset,
+            false,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mac;
+    use crate::rules::Rules;
+    use crate::tetrominoe::{State, TColor, Tetrominoe};
+
+    // twelve ticks per piece: turn some, push it against the left wall and
+    // then a different distance right each time before dropping it
+    fn script(tick: usize) -> Vec<Action> {
+        let piece = tick / 12;
+        match tick % 12 {
+            0 => vec![Action::Rotate; piece % 3],
+            1 => vec![Action::Left; 5],
+            2 => vec![Action::Right; if piece < 8 { 9 } else { piece * 7 % 10 }],
+            4 => vec![Action::SoftDrop],
+            6 if piece % 8 == 3 => vec![Action::Hold],
+            10 => vec![Action::HardDrop],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plays_back_a_recorded_game() {
+        mac::use_test_key();
+        let rules = Rules {
+            hold: true,
+            ghost: true,
+            lock_delay: 3,
+            soft_drop: 2,
+            line_clear_delay: 5,
+        };
+
+        // a seeded game with a gap on the right for the first pieces to fill
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 2024);
+        for row in HEIGHT - 4..HEIGHT {
+            for col in 0..WIDTH - 2 {
+                gs.display[row][col] = Tetrominoe::new(Some(State::Landed), Some(TColor::Blue));
+            }
+        }
+
+        let mut replay = Replay::new(&gs, rules, 100);
+        let mut clearing = false;
+        for t in 0..1500 {
+            let actions = script(t);
+            replay.record(&actions);
+            if tick(&mut gs, &actions, &rules) {
+                break;
+            }
+            clearing |= gs.line_clear.is_some();
+        }
+        replay.finish(&gs);
+        assert!(clearing && gs.gamescore.lines > 0);
+
+        let path = std::env::temp_dir().join(format!(
+            "tet-ris-{}-playback.tetris-replay",
+            std::process::id()
+        ));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.rules, rules);
+
+        let mut pb = Playback::new(&loaded);
+        assert_eq!(pb.final_score, gs.gamescore.score);
+        assert_eq!(pb.result(), "Replay OK");
+
+        while !pb.is_finished() {
+            pb.step();
+        }
+        assert_eq!(pb.gs.display, gs.display);
+        assert_eq!(pb.gs.gamescore.lines, gs.gamescore.lines);
+
+        // and again from a piece in the middle
+        pb.seek(pb.checkpoints.len() / 2);
+        while !pb.is_finished() {
+            pb.step();
+        }
+        assert_eq!(pb.gs.display, gs.display);
+        assert_eq!(pb.gs.gamescore.score, gs.gamescore.score);
+    }
+}
@@ -0,0 +1,67 @@
+use crossterm::style::Color;
+
+use crate::args::Args;
+use crate::events::Event;
+use crate::frame::Frame;
+use crate::gamestate::GameState;
+use crate::theme;
+
+// action text next to the board for the latest special clear, e.g.
+// "T-SPIN DOUBLE", "B2B", "3 COMBO" and the points it scored
+#[derive(Default)]
+pub struct Popup {
+    lines: Vec<String>,
+    // ticks since it appeared
+    age: usize,
+}
+
+impl Popup {
+    // call once per tick, after the engine ran
+    pub fn update(&mut self, gs: &mut GameState) {
+        self.age = self.age.saturating_add(1);
+        if let Some(event) = gs.events.take().pop() {
+            self.lines = text(&event);
+            self.age = 0;
+        }
+    }
+
+    // shown in the HUD colour for --popup-time, dimmed over its last third
+    pub fn draw(&self, frame: &mut Frame, x: u16, y: u16, args: &Args) {
+        let shown = self.age as u64 * args.gravity;
+        if shown >= args.popup_time {
+            return;
+        }
+        let color = if shown * 3 < args.popup_time * 2 {
+            theme::current().hud
+        } else {
+            Color::DarkGrey
+        };
+        for (row, line) in (y..).zip(&self.lines) {
+            frame.print(x, row, line, color);
+        }
+    }
+}
+
+fn text(event: &Event) -> Vec<String> {
+    let mut lines = Vec::new();
+    if event.tspin {
+        let clear = ["", " SINGLE", " DOUBLE", " TRIPLE"];
+        lines.push(format!("T-SPIN{}", clear[event.lines.min(3)]));
+    } else if event.lines == 4 {
+        lines.push("TETRIS".to_string());
+    }
+    if event.b2b {
+        lines.push("B2B".to_string());
+    }
+    // the first clear of a chain doesn't count as a combo
+    if event.combo > 1 {
+        lines.push(format!("{} COMBO", event.combo - 1));
+    }
+    if event.all_clear {
+        lines.push("ALL CLEAR".to_string());
+    }
+    if event.points > 0 {
+        lines.push(format!("+{}", event.points));
+    }
+    lines
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs,
+    io::Result,
+    time::{Duration, Instant},
+};
+
+use crate::gamestate::GameState;
+use crate::paths::recovery_path;
+use crate::savefile;
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// periodically writes the running game to the recovery file, so a crash
+// or a killed terminal doesn't lose it
+pub struct Autosave {
+    last: Instant,
+    // the last save failed
+    failing: bool,
+}
+
+impl Autosave {
+    pub fn new() -> Self {
+        Autosave {
+            last: Instant::now(),
+            failing: false,
+        }
+    }
+
+    // save if it's time to; a failure is only returned when the save before
+    // it went through, so the player isn't told again every interval
+    pub fn update(&mut self, gs: &GameState) -> Result<()> {
+        if self.last.elapsed() < AUTOSAVE_INTERVAL {
+            return Ok(());
+        }
+        let was_failing = self.failing;
+        match self.save(gs) {
+            Err(_) if was_failing => Ok(()),
+            result => result,
+        }
+    }
+
+    pub fn save(&mut self, gs: &GameState) -> Result<()> {
+        self.last = Instant::now();
+        let result = write(gs);
+        self.failing = result.is_err();
+        result
+    }
+}
+
+// write to a temporary file next to the recovery file and move it over, so
+// being killed halfway leaves the previous autosave intact
+fn write(gs: &GameState) -> Result<()> {
+    let mut gs = gs.clone();
+    gs.gamescore.stop_timer();
+    let data = savefile::encode(&gs)?;
+
+    let path = recovery_path();
+    let tmp = path.with_extension("tmp");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, &path).inspect_err(|_| {
+        fs::remove_file(&tmp).ok();
+    })
+}
+
+// the game left behind by a crash, if any
+pub fn interrupted() -> Option<GameState> {
+    let path = recovery_path();
+    if !path.exists() {
+        return None;
+    }
+
+    match GameState::deserial(&path) {
+        Ok(gs) if !gs.is_game_over => Some(gs),
+        _ => {
+            clear();
+            None
+        }
+    }
+}
+
+// the game ended normally, nothing to recover
+pub fn clear() {
+    fs::remove_file(recovery_path()).ok();
+}
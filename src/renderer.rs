@@ -0,0 +1,276 @@
+use crossterm::{
+    cursor::Hide,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+use std::{
+    io::{stdout, Stdout, Write},
+    sync::Mutex,
+};
+
+use crate::layout;
+
+// something the game screen can be drawn on; coordinates are relative to the
+// top left corner of the game screen
+pub trait Renderer: Send {
+    // blank the whole screen
+    fn clear(&mut self);
+
+    fn print(&mut self, x: u16, y: u16, text: &str, fg: Color, reverse: bool);
+
+    // end of a frame: show everything printed since the last call
+    fn flush(&mut self);
+}
+
+static RENDERER: Mutex<Option<Box<dyn Renderer>>> = Mutex::new(None);
+
+pub fn set(renderer: Box<dyn Renderer>) {
+    *RENDERER.lock().unwrap() = Some(renderer);
+}
+
+// draw with the current renderer; the terminal unless another one was set
+pub fn with<T>(draw: impl FnOnce(&mut dyn Renderer) -> T) -> T {
+    let mut renderer = RENDERER.lock().unwrap();
+    draw(
+        renderer
+            .get_or_insert_with(|| Box::new(Terminal::new()))
+            .as_mut(),
+    )
+}
+
+// the terminal, through crossterm; the game screen is centred in it
+pub struct Terminal {
+    out: Stdout,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Terminal { out: stdout() }
+    }
+}
+
+impl Renderer for Terminal {
+    fn clear(&mut self) {
+        self.out
+            .queue(Clear(ClearType::All))
+            .unwrap()
+            .queue(Hide)
+            .unwrap();
+    }
+
+    fn print(&mut self, x: u16, y: u16, text: &str, fg: Color, reverse: bool) {
+        self.out.queue(layout::at(x, y)).unwrap();
+        if reverse {
+            self.out.queue(SetAttribute(Attribute::Reverse)).unwrap();
+        }
+        self.out
+            .queue(SetForegroundColor(fg))
+            .unwrap()
+            .queue(Print(text))
+            .unwrap()
+            .queue(SetAttribute(Attribute::Reset))
+            .unwrap()
+            .queue(ResetColor)
+            .unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.out.flush().unwrap();
+    }
+}
+
+// the screen as plain text in memory, without colours; every flush keeps a
+// copy in `frames`
+pub struct Memory {
+    width: u16,
+    screen: Vec<Vec<char>>,
+    pub frames: Vec<String>,
+}
+
+impl Memory {
+    pub fn new(width: u16, height: u16) -> Self {
+        Memory {
+            width,
+            screen: vec![vec![' '; width as usize]; height as usize],
+            frames: Vec::new(),
+        }
+    }
+
+    // what the screen shows right now, one line per row
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for row in &self.screen {
+            text.push_str(row.iter().collect::<String>().trim_end());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl Renderer for Memory {
+    fn clear(&mut self) {
+        for row in &mut self.screen {
+            row.fill(' ');
+        }
+    }
+
+    // text past the edge of the screen is cut off
+    fn print(&mut self, x: u16, y: u16, text: &str, _fg: Color, _reverse: bool) {
+        if let Some(row) = self.screen.get_mut(y as usize) {
+            for (x, ch) in (x..self.width).zip(text.chars()) {
+                row[x as usize] = ch;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        self.frames.push(self.text());
+    }
+}
+
+// frames as plain text written to `out`, separated by form feeds
+pub struct Text<W: Write + Send> {
+    screen: Memory,
+    out: W,
+}
+
+impl<W: Write + Send> Text<W> {
+    pub fn new(out: W, width: u16, height: u16) -> Self {
+        Text {
+            screen: Memory::new(width, height),
+            out,
+        }
+    }
+}
+
+impl<W: Write + Send> Renderer for Text<W> {
+    fn clear(&mut self) {
+        self.screen.clear();
+    }
+
+    fn print(&mut self, x: u16, y: u16, text: &str, fg: Color, reverse: bool) {
+        self.screen.print(x, y, text, fg, reverse);
+    }
+
+    fn flush(&mut self) {
+        self.screen.flush();
+        for frame in self.screen.frames.drain(..) {
+            writeln!(self.out, "{}\x0c", frame).unwrap();
+        }
+        self.out.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use crate::events::Event;
+    use crate::gamestate::GameState;
+    use crate::input::Action;
+    use crate::popup::Popup;
+    use crate::rules::Rules;
+    use crate::tetlib::{render, tick};
+    use crate::{HEIGHT, WIDTH};
+    use clap::Parser;
+    use std::sync::Arc;
+
+    // the in-memory screen and the number of prints to it, shared with the
+    // test while the game draws on it
+    struct Shared(Arc<Mutex<(Memory, usize)>>);
+
+    impl Renderer for Shared {
+        fn clear(&mut self) {
+            self.0.lock().unwrap().0.clear();
+        }
+
+        fn print(&mut self, x: u16, y: u16, text: &str, fg: Color, reverse: bool) {
+            let mut screen = self.0.lock().unwrap();
+            screen.0.print(x, y, text, fg, reverse);
+            screen.1 += 1;
+        }
+
+        fn flush(&mut self) {
+            self.0.lock().unwrap().0.flush();
+        }
+    }
+
+    // the first piece dropped one column left, the second one held, and a
+    // tetris on the HUD; the walls are drawn separately
+    const SCREEN: [&str; 20] = [
+        "  Hold:       . . . . .██ . . . .       Score: 0",
+        "              . . . . .██ . . . .",
+        "    ██        . . . . .██ . . . .       Level: 0",
+        "    ██        . . . . .██ . . . .",
+        "  ████        . . . . . . . . . .       Time: 0:00",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .       Next:",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .         ████",
+        "              . . . . . . . . . .         ████",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .",
+        "              . . . . . . . . . .       TETRIS",
+        "              . . . . .// . . . .       +1200",
+        "              . . . . .// . . . .",
+        "              . . . .██// . . . .",
+        "              . . . .██// . . . .",
+        "              . . . .████ . . . .",
+    ];
+
+    #[test]
+    fn renders_a_game_into_memory() {
+        let args = Args::parse_from(["tet-ris"]);
+        let rules = Rules::from_args(&args);
+        layout::set_size(false, false);
+        let (width, height) = layout::size();
+        let screen = Arc::new(Mutex::new((Memory::new(width, height), 0)));
+        set(Box::new(Shared(screen.clone())));
+
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        tick(&mut gs, &[Action::Left, Action::HardDrop], &rules);
+        tick(&mut gs, &[Action::Hold], &rules);
+        gs.events.push(Event {
+            lines: 4,
+            tspin: false,
+            combo: 1,
+            b2b: false,
+            all_clear: false,
+            points: 1200,
+        });
+        let mut popup = Popup::default();
+        popup.update(&mut gs);
+
+        render(&mut gs, true, &args, &popup);
+        {
+            let screen = screen.lock().unwrap();
+            let text = screen.0.text();
+            let rows: Vec<_> = text.lines().collect();
+            assert_eq!(rows.len(), height as usize);
+            assert_eq!(rows[1..21], SCREEN);
+            assert!(rows[0].is_empty() && rows[21..].iter().all(|row| row.is_empty()));
+            assert_eq!(screen.0.frames, [text]);
+        }
+
+        // nothing changed, so nothing is printed
+        let printed = screen.lock().unwrap().1;
+        render(&mut gs, false, &args, &popup);
+        {
+            let screen = screen.lock().unwrap();
+            assert_eq!(screen.1, printed);
+            assert_eq!(screen.0.frames.len(), 2);
+            assert_eq!(screen.0.frames[0], screen.0.frames[1]);
+        }
+
+        // a move only redraws what it changed
+        tick(&mut gs, &[Action::Right], &rules);
+        render(&mut gs, false, &args, &popup);
+        let screen = screen.lock().unwrap();
+        let moved = screen.1 - printed;
+        assert!(moved > 0 && moved < printed);
+        assert_ne!(screen.0.frames[1], screen.0.frames[2]);
+    }
+}
@@ -0,0 +1,234 @@
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::gamestate::GameState;
+use crate::input::Action;
+use crate::mac;
+use crate::paths::replay_dir;
+use crate::rules::Rules;
+use crate::savefile;
+
+// Replays are sealed (see mac.rs) under the magic "TTRP", with a bincode
+// encoded Replay as the payload. The starting game is embedded as a
+// complete save file (see savefile.rs), so changes to GameState don't need
+// a new replay version.
+const MAGIC: &[u8; 4] = b"TTRP";
+pub const VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    pub tick_ms: u64,
+    #[serde(with = "embedded_save")]
+    pub start: GameState,
+    // engine actions by tick; a tick can have several
+    pub inputs: Vec<(usize, Action)>,
+    pub ticks: usize,
+    pub final_score: usize,
+}
+
+impl Replay {
+    pub fn new(gs: &GameState, rules: Rules, tick_ms: u64) -> Self {
+        Replay {
+            seed: gs.bag.seed(),
+            rules,
+            tick_ms,
+            start: gs.clone(),
+            inputs: Vec::new(),
+            ticks: 0,
+            final_score: gs.gamescore.score,
+        }
+    }
+
+    // record the actions of the current tick and advance to the next
+    pub fn record(&mut self, actions: &[Action]) {
+        for &action in actions.iter().filter(|action| action.is_engine()) {
+            self.inputs.push((self.ticks, action));
+        }
+        self.ticks += 1;
+    }
+
+    pub fn finish(&mut self, gs: &GameState) {
+        self.final_score = gs.gamescore.score;
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let payload = serialize(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        fs::write(path, mac::seal(MAGIC, VERSION, &payload)?)
+    }
+
+    // write to a new timestamped file in the replay directory
+    pub fn save_new(&self) -> Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = replay_dir().join(format!("{}.tetris-replay", timestamp));
+        self.save(&path)?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        match mac::open(MAGIC, &data)? {
+            (VERSION, payload) => from_bincode(payload),
+            (version, _) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported replay version {}", version),
+            )),
+        }
+    }
+
+    // actions recorded for the given tick; `cursor` is advanced past them
+    pub fn input_at(&self, tick: usize, cursor: &mut usize) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Some(&(t, action)) = self.inputs.get(*cursor) {
+            if t != tick {
+                break;
+            }
+            actions.push(action);
+            *cursor += 1;
+        }
+        actions
+    }
+}
+
+fn from_bincode<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T> {
+    deserialize(payload).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// the starting game as a save file, so it is versioned and migrated along
+// with saves
+mod embedded_save {
+    use super::*;
+    use serde::{de, ser, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        gs: &GameState,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let data = savefile::encode(gs).map_err(ser::Error::custom)?;
+        serializer.serialize_bytes(&data)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<GameState, D::Error> {
+        let data = Vec::<u8>::deserialize(deserializer)?;
+        savefile::decode(&data).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HEIGHT, WIDTH};
+
+    // a file in the temp directory that is removed when the test is done
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("tet-ris-{}-{}", std::process::id(), name))
+                .with_extension("tetris-replay");
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn sample() -> Replay {
+        let rules = Rules {
+            hold: true,
+            ghost: false,
+            lock_delay: 2,
+            soft_drop: 3,
+            line_clear_delay: 4,
+        };
+        let mut replay = Replay::new(&GameState::seeded(WIDTH, HEIGHT, 99), rules, 250);
+        replay.record(&[Action::Left, Action::Pause, Action::Rotate]);
+        replay.record(&[]);
+        replay.record(&[Action::HardDrop]);
+        replay.final_score = 40;
+        replay
+    }
+
+    fn error(file: &TempFile) -> String {
+        Replay::load(&file.0).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        mac::use_test_key();
+        let file = TempFile::new("round-trip");
+        sample().save(&file.0).unwrap();
+
+        let replay = Replay::load(&file.0).unwrap();
+        assert_eq!(replay.seed, 99);
+        assert_eq!(replay.rules, sample().rules);
+        assert_eq!(replay.tick_ms, 250);
+        assert_eq!(replay.ticks, 3);
+        assert_eq!(replay.final_score, 40);
+        assert_eq!(
+            savefile::encode(&replay.start).unwrap(),
+            savefile::encode(&sample().start).unwrap()
+        );
+
+        // pausing isn't recorded
+        let mut cursor = 0;
+        assert_eq!(
+            replay.input_at(0, &mut cursor),
+            [Action::Left, Action::Rotate]
+        );
+        assert!(replay.input_at(1, &mut cursor).is_empty());
+        assert_eq!(replay.input_at(2, &mut cursor), [Action::HardDrop]);
+        assert_eq!(cursor, replay.inputs.len());
+    }
+
+    #[test]
+    fn rejects_a_modified_replay() {
+        mac::use_test_key();
+        let file = TempFile::new("modified");
+        sample().save(&file.0).unwrap();
+
+        // bump the final score, keeping the checksum consistent
+        let mut data = fs::read(&file.0).unwrap();
+        let last = data.len() - 1;
+        data[last] += 1;
+        let checksum = crc32fast::hash(&data[mac::HEADER_LEN..]);
+        data[6..10].copy_from_slice(&checksum.to_le_bytes());
+        fs::write(&file.0, &data).unwrap();
+        assert!(error(&file).contains("modified outside tet-ris"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        mac::use_test_key();
+        let file = TempFile::new("other");
+        savefile::encode(&sample().start)
+            .and_then(|data| fs::write(&file.0, data))
+            .unwrap();
+        assert_eq!(error(&file), "unknown file format");
+
+        let payload = serialize(&sample()).unwrap();
+        fs::write(&file.0, mac::seal(MAGIC, VERSION + 1, &payload).unwrap()).unwrap();
+        assert_eq!(
+            error(&file),
+            format!("unsupported replay version {}", VERSION + 1)
+        );
+    }
+}
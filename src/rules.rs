@@ -0,0 +1,58 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::args::Args;
+
+// settings that change how the game plays out; stored in replays so they
+// can be simulated the same way again
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    pub hold: bool,
+    pub ghost: bool,
+    // ticks a piece can rest on the stack before gravity or a soft drop
+    // locks it
+    pub lock_delay: usize,
+    // rows moved per soft drop
+    pub soft_drop: usize,
+    // ticks full rows stay on the board before they are cleared
+    pub line_clear_delay: usize,
+}
+
+impl Rules {
+    pub fn from_args(args: &Args) -> Self {
+        Rules {
+            hold: !args.hold,
+            ghost: !args.ghost,
+            lock_delay: (args.lock_delay / args.gravity.max(1)) as usize,
+            soft_drop: args.soft_drop as usize,
+            line_clear_delay: (args.line_clear_delay / args.gravity.max(1)) as usize,
+        }
+    }
+}
+
+// game modes that keep their own high score table
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Marathon,
+    Sirtet,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 2] = [Mode::Marathon, Mode::Sirtet];
+
+    pub fn from_args(args: &Args) -> Self {
+        if args.sirtet {
+            Mode::Sirtet
+        } else {
+            Mode::Marathon
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sirtet => "sirtet",
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+use crate::bag::Bag;
+use crate::events::Events;
+use crate::gamescore::GameScore;
+use crate::gamestate::GameState;
+use crate::mac;
+use crate::stats::Stats;
+use crate::tetrominoe::Tetrominoe;
+
+// Saves are sealed (see mac.rs) under the magic "TTRS", with a bincode
+// encoded GameState as the payload.
+//
+// Saves from tet-ris 0.6.4 and earlier have no header at all and are
+// treated as version 0. Whenever the layout of GameState (or anything it
+// contains) changes, bump VERSION and add a migration from the old layout;
+// replays store their starting game in this format too, so they pick up
+// the migrations for free. Games migrated from version 0, which had no MAC,
+// are marked untrusted.
+const MAGIC: &[u8; 4] = b"TTRS";
+pub const VERSION: u16 = 1;
+
+pub fn encode(gs: &GameState) -> Result<Vec<u8>> {
+    let payload = serialize(gs).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    mac::seal(MAGIC, VERSION, &payload)
+}
+
+pub fn decode(data: &[u8]) -> Result<GameState> {
+    if !data.starts_with(MAGIC) {
+        return migrate_v0(data);
+    }
+
+    match mac::open(MAGIC, data)? {
+        (VERSION, payload) => from_bincode(payload),
+        (version, _) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported save version {}", version),
+        )),
+    }
+}
+
+fn from_bincode<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T> {
+    deserialize(payload).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// version 0: headerless saves from tet-ris 0.6.4 and earlier, with a score
+// that has no line count
+
+#[derive(Serialize, Deserialize)]
+struct GameScoreV0 {
+    score: usize,
+    level: usize,
+    elapsed_time: Duration,
+}
+
+impl From<GameScoreV0> for GameScore {
+    fn from(old: GameScoreV0) -> Self {
+        let mut gamescore = GameScore::new();
+        gamescore.score = old.score;
+        gamescore.level = old.level;
+        gamescore.elapsed_time = old.elapsed_time;
+        gamescore
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BagV0 {
+    pieces: Vec<char>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameStateV0 {
+    display: Vec<Vec<Tetrominoe>>,
+    active_piece: Tetrominoe,
+    gamescore: GameScoreV0,
+    hold_piece: Option<Tetrominoe>,
+    next_piece: Tetrominoe,
+    counter: usize,
+    is_game_over: bool,
+    bag: BagV0,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameWrapperV0 {
+    game: GameStateV0,
+    // DefaultHasher output, which isn't stable across Rust versions, so it
+    // can't be checked here
+    _hash: u64,
+}
+
+fn migrate_v0(data: &[u8]) -> Result<GameState> {
+    let old: GameWrapperV0 = from_bincode(data)?;
+    let game = old.game;
+
+    Ok(GameState {
+        display: game.display,
+        active_piece: game.active_piece,
+        gamescore: game.gamescore.into(),
+        hold_piece: game.hold_piece,
+        next_piece: game.next_piece,
+        counter: game.counter,
+        lock_timer: 0,
+        line_clear: None,
+        locked: Vec::new(),
+        lock_age: 0,
+        events: Events::default(),
+        is_game_over: game.is_game_over,
+        bag: Bag::resume(game.bag.pieces),
+        stats: Stats::default(),
+        untrusted: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrominoe::State;
+    use crate::{HEIGHT, WIDTH};
+
+    fn error(data: &[u8]) -> String {
+        decode(data).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn decodes_a_version_0_save() {
+        // written by tet-ris 0.6.4: one O piece on the floor, score 1200
+        let gs = decode(include_bytes!("../testdata/save-v0.tetris")).unwrap();
+
+        let landed: Vec<_> = (0..HEIGHT)
+            .flat_map(|row| (0..WIDTH).map(move |col| (row, col)))
+            .filter(|&(row, col)| gs.display[row][col].game_state == State::Landed)
+            .collect();
+        assert_eq!(landed, [(18, 6), (18, 7), (19, 6), (19, 7)]);
+        assert_eq!(gs.active_piece.ptype, 'T');
+        assert_eq!(gs.next_piece.ptype, 'J');
+        assert!(gs.hold_piece.is_none());
+        assert_eq!(gs.gamescore.score, 1200);
+        assert_eq!(gs.gamescore.level, 1);
+        assert_eq!(gs.gamescore.lines, 0);
+        assert_eq!(gs.gamescore.elapsed_time, Duration::from_secs(83));
+        assert_eq!(gs.stats.pieces, 0);
+        assert!(gs.untrusted);
+        gs.validate().unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        mac::use_test_key();
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 42);
+        gs.gamescore.score = 300;
+        gs.stats.pieces = 5;
+        let data = encode(&gs).unwrap();
+        assert_eq!(&data[..4], MAGIC);
+        assert_eq!(u16::from_le_bytes([data[4], data[5]]), VERSION);
+
+        let decoded = decode(&data).unwrap();
+        assert_eq!(encode(&decoded).unwrap(), data);
+        assert_eq!(decoded.bag.seed(), 42);
+        assert_eq!(decoded.gamescore.score, 300);
+        assert_eq!(decoded.stats.pieces, 5);
+        assert!(!decoded.untrusted);
+    }
+
+    #[test]
+    fn keeps_the_untrusted_flag() {
+        mac::use_test_key();
+        let mut gs = GameState::seeded(WIDTH, HEIGHT, 1);
+        gs.untrusted = true;
+        assert!(decode(&encode(&gs).unwrap()).unwrap().untrusted);
+    }
+
+    #[test]
+    fn rejects_a_flipped_byte() {
+        mac::use_test_key();
+        let mut data = encode(&GameState::seeded(WIDTH, HEIGHT, 7)).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert_eq!(error(&data), "file is corrupted");
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        mac::use_test_key();
+        let payload = serialize(&GameState::seeded(WIDTH, HEIGHT, 7)).unwrap();
+        let data = mac::seal(MAGIC, VERSION + 1, &payload).unwrap();
+        assert_eq!(
+            error(&data),
+            format!("unsupported save version {}", VERSION + 1)
+        );
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(unix)]
+use signal_hook::consts::SIGHUP;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use crate::tetlib::restore_terminal;
+
+// number of the last signal received, 0 if none
+static RECEIVED: OnceLock<Arc<AtomicUsize>> = OnceLock::new();
+
+#[cfg(unix)]
+const SIGNALS: [i32; 3] = [SIGINT, SIGTERM, SIGHUP];
+#[cfg(not(unix))]
+const SIGNALS: [i32; 2] = [SIGINT, SIGTERM];
+
+// only for the interactive screens, which poll for signals; everything else
+// keeps the default handling and is simply killed
+pub fn register() {
+    let received = RECEIVED.get_or_init(|| Arc::new(AtomicUsize::new(0)));
+    for signal in SIGNALS {
+        signal_hook::flag::register_usize(signal, Arc::clone(received), signal as usize).unwrap();
+    }
+}
+
+pub fn received() -> Option<i32> {
+    match RECEIVED.get()?.load(Ordering::Relaxed) {
+        0 => None,
+        signal => Some(signal as i32),
+    }
+}
+
+// put the terminal back and exit with the conventional 128 + signal status
+pub fn exit(signal: i32) -> ! {
+    restore_terminal();
+    process::exit(128 + signal)
+}
+
+// exit if a signal arrived; for places with no game state to save, or where
+// it was saved already
+pub fn check() {
+    if let Some(signal) = received() {
+        exit(signal);
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Local};
+use crossterm::{event::KeyCode, style::Color};
+use std::{cmp::Reverse, fs, io::Result, path::PathBuf, time::SystemTime};
+
+use crate::gamestate::{confirmation, GameState};
+use crate::keys::{self, Nav};
+use crate::paths::{slot_dir, slot_path};
+use crate::renderer;
+use crate::tetlib::{prompt_text, read_key};
+use crate::{HEIGHT, WIDTH};
+
+const VISIBLE_SLOTS: usize = 15;
+
+pub struct Slot {
+    pub name: String,
+    pub path: PathBuf,
+    pub game: Option<GameState>,
+    pub modified: Option<SystemTime>,
+}
+
+impl Slot {
+    fn read(path: PathBuf) -> Self {
+        Slot {
+            name: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            game: GameState::deserial(&path).ok(),
+            modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+            path,
+        }
+    }
+}
+
+pub enum Choice {
+    Load(PathBuf),
+    New,
+    Quit,
+}
+
+// all save slots, most recently saved first
+pub fn list() -> Vec<Slot> {
+    let mut slots: Vec<Slot> = match fs::read_dir(slot_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "tetris"))
+            .map(Slot::read)
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    slots.sort_by_key(|slot| Reverse(slot.modified));
+    slots
+}
+
+fn rename(slot: &Slot, name: &str) -> Result<()> {
+    fs::rename(&slot.path, slot_path(name))
+}
+
+// let the player pick a slot to continue, or start a new game
+pub fn manager() -> Choice {
+    let mut selected = 0;
+    let mut status = String::new();
+    loop {
+        let slots = list();
+        if slots.is_empty() {
+            return Choice::New;
+        }
+        selected = selected.min(slots.len() - 1);
+        draw(&slots, selected, &status);
+        status.clear();
+
+        let slot = &slots[selected];
+        let Some(code) = read_key() else {
+            return Choice::Quit;
+        };
+        match (code, keys::nav(code)) {
+            (KeyCode::Char('n'), _) => return Choice::New,
+            (KeyCode::Char('d') | KeyCode::Delete, _) => {
+                if !confirmation(&format!("Delete {}?", slot.name)) {
+                    continue;
+                }
+                if let Err(e) = fs::remove_file(&slot.path) {
+                    status = format!("Failed to delete {}: {}", slot.name, e);
+                }
+            }
+            (KeyCode::Char('r'), _) => {
+                if let Some(name) = prompt_text(WIDTH as u16, HEIGHT as u16, "Name") {
+                    if slot_path(&name).exists() && !confirmation("Overwrite save?") {
+                        continue;
+                    }
+                    if let Err(e) = rename(slot, &name) {
+                        status = format!("Failed to rename {}: {}", slot.name, e);
+                    }
+                }
+            }
+            (_, Some(Nav::Up)) => selected = selected.saturating_sub(1),
+            (_, Some(Nav::Down)) => selected += 1,
+            (_, Some(Nav::Select)) if slot.game.is_none() => {
+                status = format!("{} can't be loaded: the save is unreadable", slot.name);
+            }
+            (_, Some(Nav::Select)) => return Choice::Load(slot.path.clone()),
+            (_, Some(Nav::Back)) => return Choice::Quit,
+            _ => (),
+        }
+    }
+}
+
+fn draw(slots: &[Slot], selected: usize, status: &str) {
+    renderer::with(|out| {
+        out.clear();
+        out.print(2, 1, "S A V E S", Color::Reset, false);
+        out.print(
+            2,
+            3,
+            &format!(
+                "  {:<12} {:>8} {:>5} {:>7}  {}",
+                "Name", "Score", "Level", "Time", "Saved"
+            ),
+            Color::Reset,
+            false,
+        );
+
+        let first = (selected + 1).saturating_sub(VISIBLE_SLOTS);
+        for (row, (i, slot)) in (4..).zip(slots.iter().enumerate().skip(first).take(VISIBLE_SLOTS))
+        {
+            let stats = match &slot.game {
+                Some(gs) => {
+                    let time = gs.gamescore.get_time();
+                    format!(
+                        "{:>8} {:>5} {:>4}:{:02}",
+                        gs.gamescore.score,
+                        gs.gamescore.level,
+                        time / 60,
+                        time % 60
+                    )
+                }
+                None => format!("{:<22}", "(unreadable)"),
+            };
+            let saved = slot
+                .modified
+                .map(|m| {
+                    DateTime::<Local>::from(m)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+
+            out.print(
+                2,
+                row,
+                &format!(
+                    "{} {:<12} {}  {}",
+                    if i == selected { ">" } else { " " },
+                    slot.name,
+                    stats,
+                    saved
+                ),
+                Color::Reset,
+                i == selected,
+            );
+        }
+
+        out.print(
+            2,
+            VISIBLE_SLOTS as u16 + 5,
+            "Enter: load  n: new game  r: rename  d: delete  Esc: quit",
+            Color::Reset,
+            false,
+        );
+        out.print(2, VISIBLE_SLOTS as u16 + 7, status, Color::Reset, false);
+        out.flush();
+    });
+}
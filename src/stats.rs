@@ -0,0 +1,222 @@
+use crossterm::{event::KeyCode, style::Color};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::args::Args;
+use crate::frame::Frame;
+use crate::gamestate::GameState;
+use crate::renderer;
+use crate::tetlib::{put_block, read_key};
+use crate::tetrominoe::Tetrominoe;
+use crate::WIDTH;
+
+const PIECES: [char; 7] = ['I', 'J', 'L', 'O', 'S', 'T', 'Z'];
+const CLEARS: [&str; 4] = ["Single", "Double", "Triple", "Tetris"];
+
+// lines sent to an opponent for each clear type
+const ATTACK: [usize; 4] = [0, 1, 2, 4];
+
+#[derive(Serialize, Deserialize, Clone, Hash, Default)]
+pub struct Stats {
+    pub pieces: usize,
+    pub keys: usize,
+    // singles, doubles, triples and tetrises
+    pub clears: [usize; 4],
+    pub attack: usize,
+    // pieces that entered play, in IJLOSTZ order
+    pub piece_counts: [usize; 7],
+    // pieces since the last I piece, and the longest such stretch
+    pub drought: usize,
+    pub max_drought: usize,
+    // consecutive pieces that cleared lines, and the longest such chain
+    pub combo: usize,
+    pub best_combo: usize,
+    // a piece locked this tick and its line clears haven't been counted yet
+    #[serde(skip)]
+    locked: bool,
+}
+
+impl Stats {
+    pub fn key(&mut self) {
+        self.keys += 1;
+    }
+
+    pub fn placed(&mut self) {
+        self.pieces += 1;
+        self.locked = true;
+    }
+
+    pub fn spawned(&mut self, piece: char) {
+        if let Some(i) = PIECES.iter().position(|&p| p == piece) {
+            self.piece_counts[i] += 1;
+        }
+        if piece == 'I' {
+            self.drought = 0;
+        } else {
+            self.drought += 1;
+            self.max_drought = self.max_drought.max(self.drought);
+        }
+    }
+
+    // called every tick with the lines just cleared, if any
+    pub fn cleared(&mut self, lines: usize) {
+        if (1..=4).contains(&lines) {
+            self.clears[lines - 1] += 1;
+            self.attack += ATTACK[lines - 1];
+        }
+
+        if self.locked {
+            self.combo = if lines > 0 { self.combo + 1 } else { 0 };
+            self.best_combo = self.best_combo.max(self.combo);
+            self.locked = false;
+        }
+    }
+
+    // pieces per second
+    pub fn pps(&self, elapsed: Duration) -> f64 {
+        per(self.pieces as f64, elapsed.as_secs_f64())
+    }
+
+    // keys per piece
+    pub fn kpp(&self) -> f64 {
+        per(self.keys as f64, self.pieces as f64)
+    }
+
+    // attack per minute
+    pub fn apm(&self, elapsed: Duration) -> f64 {
+        per(self.attack as f64 * 60.0, elapsed.as_secs_f64())
+    }
+
+    // statistics panel with its top left corner at (x, y)
+    fn draw(&self, frame: &mut Frame, elapsed: Duration, x: u16, y: u16, chars: &str) {
+        let mut lines = vec![
+            format!("Pieces {:>7}", self.pieces),
+            format!("PPS    {:>7.2}", self.pps(elapsed)),
+            format!("KPP    {:>7.2}", self.kpp()),
+            format!("APM    {:>7.1}", self.apm(elapsed)),
+            String::new(),
+        ];
+        for (name, count) in CLEARS.iter().zip(self.clears) {
+            lines.push(format!("{:<6} {:>7}", name, count));
+        }
+        lines.push(String::new());
+        lines.push(format!("Drought {:>6}", self.drought));
+        lines.push(format!("Longest {:>6}", self.max_drought));
+        lines.push(String::new());
+
+        for (row, line) in (y..).zip(&lines) {
+            frame.print(x, row, line, Color::Reset);
+        }
+
+        // piece distribution, like the NES statistics panel
+        for (row, (&piece, count)) in
+            (y + lines.len() as u16..).zip(PIECES.iter().zip(self.piece_counts))
+        {
+            put_block(frame, x, row, Tetrominoe::from(piece, None).color, chars);
+            frame.print(
+                x + 2,
+                row,
+                &format!(" {} {:>8}", piece, count),
+                Color::Reset,
+            );
+        }
+    }
+}
+
+// full screen statistics, until a key is pressed
+pub fn screen(gs: &GameState, args: &Args) {
+    let draw = || {
+        let mut frame = Frame::new(40, 26);
+        frame.print(2, 1, "S T A T I S T I C S", Color::Reset);
+        gs.stats
+            .draw(&mut frame, gs.gamescore.elapsed_time, 2, 3, &args.chars);
+        frame.print(2, 25, "Press any key", Color::Reset);
+        renderer::with(|out| {
+            out.clear();
+            frame.flush(out);
+        });
+    };
+    draw();
+    while read_key() == Some(KeyCode::Null) {
+        draw();
+    }
+}
+
+// panel to the right of the next piece
+pub fn panel(frame: &mut Frame, gs: &GameState, chars: &str) {
+    gs.stats.draw(
+        frame,
+        gs.gamescore.elapsed_time,
+        WIDTH as u16 * 4 + 14,
+        1,
+        chars,
+    );
+}
+
+fn per(amount: f64, over: f64) -> f64 {
+    if over > 0.0 {
+        amount / over
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates() {
+        let stats = Stats {
+            pieces: 30,
+            keys: 90,
+            attack: 12,
+            ..Stats::default()
+        };
+        let minute = Duration::from_secs(60);
+        assert_eq!(stats.pps(minute), 0.5);
+        assert_eq!(stats.kpp(), 3.0);
+        assert_eq!(stats.apm(minute), 12.0);
+        assert_eq!(stats.apm(Duration::from_secs(30)), 24.0);
+
+        // nothing to divide by at the start of a game
+        assert_eq!(stats.pps(Duration::ZERO), 0.0);
+        assert_eq!(stats.apm(Duration::ZERO), 0.0);
+        assert_eq!(Stats::default().kpp(), 0.0);
+    }
+
+    #[test]
+    fn tracks_droughts() {
+        let mut stats = Stats::default();
+        for piece in "TSZIOLJJI".chars() {
+            stats.spawned(piece);
+        }
+        assert_eq!(stats.piece_counts, [2, 2, 1, 1, 1, 1, 1]);
+        assert_eq!(stats.drought, 0);
+        assert_eq!(stats.max_drought, 4);
+
+        stats.spawned('O');
+        assert_eq!(stats.drought, 1);
+        assert_eq!(stats.max_drought, 4);
+    }
+
+    #[test]
+    fn tracks_combos() {
+        let mut stats = Stats::default();
+        for lines in [1, 2, 0, 4, 3, 1, 0] {
+            stats.placed();
+            stats.cleared(lines);
+        }
+        assert_eq!(stats.pieces, 7);
+        assert_eq!(stats.clears, [2, 1, 1, 1]);
+        assert_eq!(stats.attack, 1 + 2 + 4);
+        assert_eq!(stats.combo, 0);
+        assert_eq!(stats.best_combo, 3);
+
+        // ticks without a locked piece leave the combo alone
+        stats.placed();
+        stats.cleared(1);
+        stats.cleared(0);
+        assert_eq!(stats.combo, 1);
+    }
+}
@@ -0,0 +1,117 @@
+use crossterm::style::Color;
+
+use crate::args::Args;
+use crate::gamestate::{confirmation, GameState};
+use crate::highscores;
+use crate::keys::{self, Nav};
+use crate::paths::replay_path;
+use crate::renderer;
+use crate::replay::Replay;
+use crate::rules::Mode;
+use crate::stats;
+use crate::tetlib::{prompt_text, read_key};
+use crate::{HEIGHT, WIDTH};
+
+const ITEMS: [&str; 5] = ["Retry", "Save replay", "View stats", "High scores", "Quit"];
+
+pub enum Choice {
+    Retry,
+    Quit,
+}
+
+// results screen shown after game over
+pub fn show(
+    gs: &GameState,
+    mode: Mode,
+    rank: Option<usize>,
+    replay: &Replay,
+    args: &Args,
+) -> Choice {
+    let mut selected = 0;
+    let mut status = String::new();
+    loop {
+        draw(gs, mode, rank, selected, &status);
+        let Some(code) = read_key() else {
+            return Choice::Quit;
+        };
+        match keys::nav(code) {
+            Some(Nav::Up) => selected = selected.saturating_sub(1),
+            Some(Nav::Down) => selected = (selected + 1).min(ITEMS.len() - 1),
+            Some(Nav::Back) => return Choice::Quit,
+            Some(Nav::Select) => match selected {
+                0 => return Choice::Retry,
+                1 => status = save_replay(replay),
+                2 => stats::screen(gs, args),
+                3 => highscores::show(mode, rank),
+                _ => return Choice::Quit,
+            },
+            None => (),
+        }
+    }
+}
+
+// keep the replay under a name of the player's choosing
+fn save_replay(replay: &Replay) -> String {
+    let Some(name) = prompt_text(WIDTH as u16, HEIGHT as u16, "Name") else {
+        return String::new();
+    };
+    let path = replay_path(&name);
+    if path.exists() && !confirmation("Overwrite replay?") {
+        return String::new();
+    }
+
+    match replay.save(&path) {
+        Ok(()) => format!("Replay saved to {}", path.display()),
+        Err(e) => format!("Failed to save replay: {}", e),
+    }
+}
+
+fn draw(gs: &GameState, mode: Mode, rank: Option<usize>, selected: usize, status: &str) {
+    let time = gs.gamescore.get_time();
+    let rank = match rank {
+        Some(rank) => format!("#{} {}", rank + 1, mode.name()),
+        None if gs.untrusted => "untrusted".to_string(),
+        None => "-".to_string(),
+    };
+    let results = [
+        ("Score", gs.gamescore.score.to_string()),
+        ("Lines", gs.gamescore.lines.to_string()),
+        ("Level", gs.gamescore.level.to_string()),
+        ("Time", format!("{}:{:02}", time / 60, time % 60)),
+        (
+            "PPS",
+            format!("{:.2}", gs.stats.pps(gs.gamescore.elapsed_time)),
+        ),
+        ("Best combo", gs.stats.best_combo.to_string()),
+        ("Rank", rank),
+    ];
+
+    renderer::with(|out| {
+        out.clear();
+        out.print(2, 1, "G A M E  O V E R", Color::Reset, false);
+
+        for (row, (label, value)) in (3..).zip(&results) {
+            out.print(
+                2,
+                row,
+                &format!("{:<12} {:>14}", label, value),
+                Color::Reset,
+                false,
+            );
+        }
+
+        let top = results.len() as u16 + 4;
+        for (row, (i, item)) in (top..).zip(ITEMS.iter().enumerate()) {
+            out.print(
+                2,
+                row,
+                &format!("{} {:<24}", if i == selected { ">" } else { " " }, item),
+                Color::Reset,
+                i == selected,
+            );
+        }
+
+        out.print(2, top + ITEMS.len() as u16 + 1, status, Color::Reset, false);
+        out.flush();
+    });
+}
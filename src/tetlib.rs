@@ -1,164 +1,199 @@
 use crossterm::{
-    cursor::{Hide, MoveTo},
+    cursor::Show,
     event::{poll, KeyEventKind},
-    style::{Color, Print, ResetColor, SetForegroundColor},
-    terminal::{Clear, ClearType},
-    QueueableCommand,
+    execute,
+    style::Color,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 
-use std::{
-    io::{stdout, Write},
-    time::Duration,
-};
+use std::{io::stdout, sync::Mutex, time::Duration};
 
 use crate::tetrominoe::Tetrominoe;
 use crate::{
-    gamestate::GameState,
+    args::{Args, ClearAnimation},
+    events,
+    frame::Frame,
+    gamestate::{GameState, LineClear},
+    input::Action,
+    keys::{self, Nav},
+    layout,
+    popup::Popup,
+    renderer,
+    rules::Rules,
+    signals, stats,
     tetrominoe::{State, TColor},
+    theme::{self, Glyphs},
+    HEIGHT, WIDTH,
 };
 
 pub const EMP: char = '.';
 
-pub fn render(
-    gs: &mut GameState,
-    is_updated: bool,
-    block_characters: &String,
-    colors: &bool,
-    sirtet: &bool,
-) {
-    if !is_updated {
-        return;
-    }
+const MAX_LEVEL: usize = 20;
+const GRAV_TICK: usize = 40;
+const LEVEL_MULT: f64 = 0.85;
 
+pub fn setup_terminal() {
+    execute!(stdout(), EnterAlternateScreen).unwrap();
+    enable_raw_mode().unwrap();
+}
+
+// also used from the panic hook, so errors are ignored rather than unwrapped
+pub fn restore_terminal() {
     let mut stdout = stdout();
-    let width: u16 = gs.display[0].len() as u16;
-    let mut current_col: u16 = if *sirtet { 3 } else { 1 };
-    let proxy_display = if *sirtet {
-        gs.display.clone().into_iter().rev().collect()
-    } else {
-        gs.display.clone()
-    };
+    disable_raw_mode().ok();
+    execute!(stdout, LeaveAlternateScreen).ok();
+    execute!(stdout, Show).ok();
+}
+
+// the game screen as last drawn; walls and overlays (menus, messages) are
+// written straight to the terminal and are not part of it
+static FRAME: Mutex<Frame> = Mutex::new(Frame::empty());
+
+// draw the hold panel, board, HUD and (with --stats) statistics panel,
+// writing only the cells that changed since the last call; `full` rewrites
+// them all, e.g. after an overlay was drawn over the board
+pub fn render(gs: &mut GameState, full: bool, args: &Args, popup: &Popup) {
+    let theme = theme::current();
+    let block_characters = &args.chars;
+    let mut frame = FRAME.lock().unwrap();
+    let (frame_width, frame_height) = layout::size();
+    frame.resize(frame_width, frame_height);
+    if full {
+        frame.invalidate();
+    }
+    frame.clear();
 
-    stdout.queue(MoveTo(width + 3, current_col)).unwrap(); // move cursor to top left
-    for row in proxy_display {
-        for ch in row {
+    let width: u16 = gs.display[0].len() as u16;
+    let height = gs.display.len();
+    let first_col: u16 = if args.sirtet { 3 } else { 1 };
+    let flashing = args.lock_flash > 0 && (gs.lock_age as u64) * args.gravity < args.lock_flash;
+
+    for (row, cells) in gs.display.iter().enumerate() {
+        let y = first_col + (if args.sirtet { height - 1 - row } else { row }) as u16;
+        for (col, ch) in cells.iter().enumerate() {
+            let x = width + 3 + col as u16 * 2;
             match ch.game_state {
-                State::Empty => {
-                    stdout.queue(Print(" .")).unwrap();
+                State::Empty => frame.print(x, y, " .", Color::Reset),
+                State::Active | State::Landed if clearing(gs, args, row, col) => {
+                    frame.print(x, y, "  ", Color::Reset)
                 }
-                State::Active | State::Landed => {
-                    let color = if !colors { ch.as_color() } else { Color::White };
-
-                    stdout
-                        .queue(SetForegroundColor(color))
-                        .unwrap()
-                        .queue(Print(block_characters))
-                        .unwrap()
-                        .queue(ResetColor)
-                        .unwrap();
+                State::Landed if flashing && gs.locked.contains(&(row, col)) => {
+                    frame.print(x, y, &theme.block(ch.color, block_characters), Color::White)
                 }
-                State::Ghost => {
-                    stdout
-                        .queue(SetForegroundColor(Color::Rgb {
-                            r: 50,
-                            g: 50,
-                            b: 50,
-                        }))
-                        .unwrap()
-                        .queue(Print("//"))
-                        .unwrap()
-                        .queue(ResetColor)
-                        .unwrap();
+                State::Active | State::Landed => {
+                    put_block(&mut frame, x, y, ch.color, block_characters)
                 }
+                State::Ghost => frame.print(x, y, &theme.ghost_chars, theme.ghost),
             }
         }
-        stdout.queue(MoveTo(width + 3, current_col + 1)).unwrap();
-        current_col += 1;
     }
 
-    // drop(proxy_display);
-
     // hold piece
-    stdout.queue(MoveTo(2, 1)).unwrap();
-    stdout.queue(Print("Hold:")).unwrap();
-    stdout.queue(MoveTo(2, 3)).unwrap();
-    match &gs.hold_piece {
-        Some(piece) => {
-            let mut blank = Tetrominoe::new(None, None);
-            let upright = blank.set(piece.ptype);
-            for row in 0..upright.shape.len() {
-                for col in 0..upright.shape[row].len() {
-                    if upright.shape[row][col] == 'a' {
-                        let color = if !colors {
-                            piece.as_color()
-                        } else {
-                            Color::White
-                        };
-
-                        stdout
-                            .queue(SetForegroundColor(color))
-                            .unwrap()
-                            .queue(Print(block_characters))
-                            .unwrap()
-                            .queue(ResetColor)
-                            .unwrap();
-                    } else {
-                        stdout.queue(Print("  ")).unwrap();
-                    }
-                }
-                stdout.queue(MoveTo(2, (row + 4) as u16)).unwrap();
-            }
-        }
-
-        None => (),
+    frame.print(2, 1, "Hold:", theme.hud);
+    if let Some(piece) = &gs.hold_piece {
+        let mut blank = Tetrominoe::new(None, None);
+        let upright = blank.set(piece.ptype);
+        put_piece(
+            &mut frame,
+            2,
+            3,
+            &upright.shape,
+            piece.color,
+            block_characters,
+        );
     }
 
     // print stats
-    stdout.queue(MoveTo(width * 4, 1)).unwrap();
-    stdout
-        .queue(Print(format!("Score: {}", gs.gamescore.score)))
-        .unwrap();
-    stdout.queue(MoveTo(width * 4, 3)).unwrap();
-    stdout
-        .queue(Print(format!("Level: {}", gs.gamescore.level)))
-        .unwrap();
-    stdout.queue(MoveTo(width * 4, 5)).unwrap();
+    let x = width * 4;
+    frame.print(x, 1, &format!("Score: {}", gs.gamescore.score), theme.hud);
+    frame.print(x, 3, &format!("Level: {}", gs.gamescore.level), theme.hud);
     gs.gamescore.update();
     let time = gs.gamescore.get_time();
-    stdout
-        .queue(Print(format!("Time: {}:{:02}", time / 60, time % 60)))
-        .unwrap();
+    frame.print(
+        x,
+        5,
+        &format!("Time: {}:{:02}", time / 60, time % 60),
+        theme.hud,
+    );
 
     // next piece
-    stdout.queue(MoveTo(width * 4, 8)).unwrap();
-    stdout.queue(Print("Next:")).unwrap();
-    stdout.queue(MoveTo(width * 4, 10)).unwrap();
-    for row in 0..gs.next_piece.shape.len() {
-        for col in 0..gs.next_piece.shape[row].len() {
-            if gs.next_piece.shape[row][col] == 'a' {
-                let color = if !colors {
-                    gs.next_piece.as_color()
-                } else {
-                    Color::White
-                };
-
-                stdout
-                    .queue(SetForegroundColor(color))
-                    .unwrap()
-                    .queue(Print(block_characters))
-                    .unwrap()
-                    .queue(ResetColor)
-                    .unwrap();
-            } else {
-                stdout.queue(Print("  ")).unwrap();
+    frame.print(x, 8, "Next:", theme.hud);
+    put_piece(
+        &mut frame,
+        x,
+        10,
+        &gs.next_piece.shape,
+        gs.next_piece.color,
+        block_characters,
+    );
+
+    // action text below the next piece
+    popup.draw(&mut frame, x, 15, args);
+
+    if args.stats {
+        stats::panel(&mut frame, gs, block_characters);
+    }
+
+    renderer::with(|out| frame.flush(out));
+}
+
+// true if the block at (row, col) is hidden by the line clear animation
+fn clearing(gs: &GameState, args: &Args, row: usize, col: usize) -> bool {
+    let Some(clear) = &gs.line_clear else {
+        return false;
+    };
+    if !clear.rows.contains(&row) {
+        return false;
+    }
+
+    match args.clear_animation {
+        ClearAnimation::Off => false,
+        // three blinks over the delay
+        ClearAnimation::Flash => clear.tick * 6 / clear.length % 2 == 1,
+        // a gap that opens up from the middle of the row
+        ClearAnimation::Collapse => {
+            let half = gs.display[row].len() / 2;
+            let gap = (clear.tick + 1) * half / clear.length;
+            col + gap >= half && col < half + gap
+        }
+    }
+}
+
+// the 'a' cells of a piece's shape, with the top left corner at (x, y)
+fn put_piece(
+    frame: &mut Frame,
+    x: u16,
+    y: u16,
+    shape: &[[char; 4]; 4],
+    color: TColor,
+    block_characters: &str,
+) {
+    for (row, cells) in (y..).zip(shape) {
+        for (col, &cell) in (x..).step_by(2).zip(cells) {
+            if cell == 'a' {
+                put_block(frame, col, row, color, block_characters);
             }
         }
-        stdout.queue(MoveTo(width * 4, (row + 11) as u16)).unwrap();
     }
+}
 
-    stdout.flush().unwrap();
+// one block of a piece, in the theme's colour and glyphs
+pub fn put_block(frame: &mut Frame, x: u16, y: u16, color: TColor, block_characters: &str) {
+    let theme = theme::current();
+    frame.print_styled(
+        x,
+        y,
+        &theme.block(color, block_characters),
+        theme.piece(color),
+        theme.glyphs == Glyphs::Letter,
+    );
+}
+
+// the screen was cleared, so the next render has to draw everything
+fn invalidate() {
+    FRAME.lock().unwrap().invalidate();
 }
 
 pub fn init(width: usize, height: usize) -> Vec<Vec<Tetrominoe>> {
@@ -168,68 +203,156 @@ pub fn init(width: usize, height: usize) -> Vec<Vec<Tetrominoe>> {
     for _ in 0..height {
         display.push(vec![Tetrominoe::default(); width]);
     }
+    display
+}
 
-    // walls
-    let mut stdout = stdout();
-    stdout.queue(Clear(ClearType::All)).unwrap();
-    stdout.queue(MoveTo(11, 1)).unwrap(); // move cursor to top left while leaving space for hold
-    for row in display.iter().enumerate() {
-        stdout.queue(Print("<!")).unwrap(); // left wall
-        for _ in row.1 {
-            stdout.queue(Print("  ")).unwrap();
+// clear the screen and draw the walls around the board
+pub fn walls(width: usize, height: usize) {
+    let wall = theme::current().wall;
+    renderer::with(|out| {
+        out.clear();
+        for row in 0..height {
+            // leave space for hold on the left
+            out.print(
+                11,
+                row as u16 + 1,
+                &format!("<!{}!>", "  ".repeat(width)),
+                wall,
+                false,
+            );
         }
-        stdout.queue(Print("!>")).unwrap(); // right wall
-        stdout.queue(MoveTo(11, (row.0 + 2) as u16)).unwrap();
-    }
-    stdout
-        .queue(Print(format!(
-            "<!{}!>\r\n",
-            "=".repeat(display[0].len() * 2)
-        )))
-        .unwrap(); // bottom wall
-    stdout
-        .queue(Print(format!(
-            "{}{}",
-            " ".repeat(13),
-            "\\/".repeat(display[0].len())
-        )))
-        .unwrap(); // bottom spikes
-    stdout.queue(Hide).unwrap(); // Hide the cursor
-    stdout.flush().unwrap();
+        out.print(
+            11,
+            height as u16 + 1,
+            &format!("<!{}!>", "=".repeat(width * 2)),
+            wall,
+            false,
+        ); // bottom wall
+        out.print(13, height as u16 + 2, &"\\/".repeat(width), wall, false); // bottom spikes
+        out.flush();
+    });
+    invalidate();
+}
 
-    display
+// walls for the mode being played, on a cleared screen
+pub fn draw_walls(sirtet: bool) {
+    if sirtet {
+        sirtet_borders(WIDTH, HEIGHT);
+    } else {
+        walls(WIDTH, HEIGHT);
+    }
 }
 
 pub fn sirtet_borders(width: usize, height: usize) {
     // walls
-    let mut stdout = stdout();
-    stdout.queue(Clear(ClearType::All)).unwrap();
-    // Start at the bottom-left of the display
-    stdout.queue(MoveTo(11, 1)).unwrap();
-
-    // Draw the spikes at the top (previously at the bottom)
-    stdout
-        .queue(Print(format!("{}{}", " ".repeat(2), "/\\".repeat(width))))
-        .unwrap();
-    stdout.queue(MoveTo(11, 2)).unwrap(); // move the cursor up
-
-    // Draw the top wall (previously at the bottom)
-    stdout
-        .queue(Print(format!("<!{}!>", "=".repeat(width * 2))))
-        .unwrap();
-    stdout.queue(MoveTo(11, 3)).unwrap(); // move the cursor up
-
-    for row_num in 0..height {
-        stdout.queue(Print("<!")).unwrap(); // left wall
-        for _ in 0..width {
-            stdout.queue(Print("  ")).unwrap();
+    let wall = theme::current().wall;
+    renderer::with(|out| {
+        out.clear();
+
+        // Draw the spikes at the top (previously at the bottom)
+        out.print(13, 1, &"/\\".repeat(width), wall, false);
+
+        // Draw the top wall (previously at the bottom)
+        out.print(
+            11,
+            2,
+            &format!("<!{}!>", "=".repeat(width * 2)),
+            wall,
+            false,
+        );
+
+        for row_num in 0..height {
+            out.print(
+                11,
+                row_num as u16 + 3,
+                &format!("<!{}!>", "  ".repeat(width)),
+                wall,
+                false,
+            );
+        }
+        out.flush();
+    });
+    invalidate();
+}
+
+// advance the game by one tick; return true on game over
+pub fn tick(gs: &mut GameState, actions: &[Action], rules: &Rules) -> bool {
+    gs.lock_age = gs.lock_age.saturating_add(1);
+
+    // full rows are on their way out; nothing moves until they are gone
+    if let Some(clear) = &mut gs.line_clear {
+        clear.tick += 1;
+        if clear.tick >= clear.length {
+            let rows = gs.line_clear.take().unwrap().rows;
+            clear_lines(gs, &rows);
+            if rules.ghost {
+                ghost_piece(gs);
+            }
+        }
+        return false;
+    }
+
+    // a resting piece only locks once its lock delay is up
+    gs.lock_timer = if resting(gs) { gs.lock_timer + 1 } else { 0 };
+    let can_lock = gs.lock_timer == 0 || gs.lock_timer > rules.lock_delay;
+
+    // gravity
+    if gs.counter >= (GRAV_TICK as f64 * LEVEL_MULT.powf(gs.gamescore.level as f64)) as usize
+        && can_lock
+    {
+        if gravity(gs) {
+            gs.is_game_over = true;
+            return true;
+        }
+        gs.counter = if gs.gamescore.level < MAX_LEVEL {
+            0
+        } else {
+            100
+        };
+    }
+
+    // handle input, in the order it was pressed
+    for &action in actions.iter().filter(|action| action.is_engine()) {
+        gs.stats.key();
+        match action {
+            Action::SoftDrop if !can_lock => (),
+            Action::SoftDrop => {
+                let pieces = gs.stats.pieces;
+                handle_input(gs, action);
+                // extra rows for faster soft drops, stopping short of locking
+                for _ in 1..rules.soft_drop {
+                    if gs.stats.pieces != pieces || resting(gs) {
+                        break;
+                    }
+                    gravity(gs);
+                }
+            }
+            Action::Hold if rules.hold => hold(gs),
+            _ => handle_input(gs, action),
         }
-        stdout.queue(Print("!>")).unwrap(); // right wall
-        stdout.queue(MoveTo(11, row_num as u16 + 4)).unwrap(); // move cursor up
     }
 
-    stdout.queue(Hide).unwrap(); // Hide the cursor
-    stdout.flush().unwrap();
+    // full line
+    full_line(gs, rules);
+
+    // ghost piece
+    if rules.ghost {
+        ghost_piece(gs);
+    }
+
+    gs.counter += 1;
+    false
+}
+
+// true if the active piece sits on the floor or on landed blocks
+fn resting(gs: &GameState) -> bool {
+    let height = gs.display.len();
+    gs.display.iter().enumerate().any(|(row, cells)| {
+        cells.iter().enumerate().any(|(col, cell)| {
+            cell.game_state == State::Active
+                && (row == height - 1 || gs.display[row + 1][col].game_state == State::Landed)
+        })
+    })
 }
 
 pub fn gravity(gs: &mut GameState) -> bool {
@@ -252,13 +375,14 @@ pub fn gravity(gs: &mut GameState) -> bool {
         }
     }
     gs.active_piece.row += 1;
+    gs.events.rotated = false;
     false
 }
 
-pub fn handle_input(gs: &mut GameState, key: char) {
+pub fn handle_input(gs: &mut GameState, action: Action) {
     let prev_display = gs.display.clone();
-    match key {
-        'l' => {
+    match action {
+        Action::Left => {
             for row in (0..gs.display.len()).rev() {
                 for col in 0..gs.display[row].len() {
                     if gs.display[row][col].game_state == State::Active {
@@ -277,7 +401,7 @@ pub fn handle_input(gs: &mut GameState, key: char) {
             }
         }
 
-        'r' => {
+        Action::Right => {
             for row in (0..gs.display.len()).rev() {
                 for col in (0..gs.display[row].len()).rev() {
                     if gs.display[row][col].game_state == State::Active {
@@ -295,18 +419,18 @@ pub fn handle_input(gs: &mut GameState, key: char) {
             gs.active_piece.col += 1;
         }
 
-        's' => {
+        Action::HardDrop => {
             // bring down piece until new piece is created
             while gs.display[0][gs.display[0].len() / 2].game_state == State::Empty {
                 gravity(gs);
             }
         }
 
-        'd' => {
+        Action::SoftDrop => {
             gravity(gs);
         }
 
-        'u' => {
+        Action::Rotate => {
             // let prev_display = gs.display.clone();
             let prev_piece = gs.active_piece;
 
@@ -349,6 +473,13 @@ pub fn handle_input(gs: &mut GameState, key: char) {
 
         _ => (),
     }
+
+    // the move went through; t-spins need the last one to be a rotation
+    match action {
+        Action::Left | Action::Right => gs.events.rotated = false,
+        Action::Rotate => gs.events.rotated = true,
+        _ => (),
+    }
 }
 
 pub fn new_piece(gs: &mut GameState, desired_piece: Option<char>) -> bool {
@@ -434,45 +565,148 @@ pub fn new_piece(gs: &mut GameState, desired_piece: Option<char>) -> bool {
     }
     gs.active_piece.set(piece);
     gs.active_piece.set_pos(0, half_width - 1);
+    gs.events.rotated = false;
     false
 }
 
 pub fn landed(gs: &mut GameState) {
-    for row in &mut gs.display {
-        for ch in row {
+    gs.locked.clear();
+    for (row, cells) in gs.display.iter_mut().enumerate() {
+        for (col, ch) in cells.iter_mut().enumerate() {
             if ch.game_state == State::Active {
                 ch.game_state = State::Landed;
+                gs.locked.push((row, col));
             }
         }
     }
+    gs.lock_age = 0;
+    gs.events.tspin = is_tspin(gs);
+    gs.stats.placed();
 }
 
-pub fn full_line(gs: &mut GameState) {
-    let mut lines: usize = 0;
-    'outer: for row in (0..gs.display.len()).rev() {
-        for ch in &gs.display[row] {
-            if ch.game_state != State::Landed {
-                continue 'outer;
-            }
-        }
+// a T turned into place, with at least three of the four corners around its
+// middle block filled (or outside the board)
+fn is_tspin(gs: &GameState) -> bool {
+    if gs.active_piece.ptype != 'T' || !gs.events.rotated {
+        return false;
+    }
+
+    // the middle block is the one next to the other three
+    let Some(&(row, col)) = gs.locked.iter().find(|&&(row, col)| {
+        gs.locked
+            .iter()
+            .filter(|&&(r, c)| row.abs_diff(r) + col.abs_diff(c) == 1)
+            .count()
+            == 3
+    }) else {
+        return false;
+    };
+
+    let filled = |r: Option<usize>, c: Option<usize>| match (r, c) {
+        (Some(r), Some(c)) => gs
+            .display
+            .get(r)
+            .and_then(|cells| cells.get(c))
+            .is_none_or(|ch| ch.game_state == State::Landed),
+        _ => true,
+    };
+    [
+        filled(row.checked_sub(1), col.checked_sub(1)),
+        filled(row.checked_sub(1), Some(col + 1)),
+        filled(Some(row + 1), col.checked_sub(1)),
+        filled(Some(row + 1), Some(col + 1)),
+    ]
+    .iter()
+    .filter(|&&filled| filled)
+    .count()
+        >= 3
+}
+
+// clear full rows right away, or leave them for the line clear delay
+pub fn full_line(gs: &mut GameState, rules: &Rules) {
+    let rows: Vec<usize> = (0..gs.display.len())
+        .rev()
+        .filter(|&row| {
+            gs.display[row]
+                .iter()
+                .all(|ch| ch.game_state == State::Landed)
+        })
+        .collect();
+
+    if rows.is_empty() && gs.events.tspin {
+        gs.events.tspin = false;
+        gs.events.push(events::Event {
+            lines: 0,
+            tspin: true,
+            combo: 0,
+            b2b: false,
+            all_clear: false,
+            points: 0,
+        });
+    }
+
+    if rows.is_empty() || rules.line_clear_delay == 0 {
+        clear_lines(gs, &rows);
+    } else {
+        gs.line_clear = Some(LineClear {
+            rows,
+            tick: 0,
+            length: rules.line_clear_delay,
+        });
+    }
+}
+
+// remove the given rows, bottom first, and score them
+fn clear_lines(gs: &mut GameState, rows: &[usize]) {
+    let lines = rows.len();
+    for &row in rows {
         gs.display.remove(row);
-        lines += 1;
     }
+    // blocks of the locked piece move down with the rows above the cleared ones
+    gs.locked = gs
+        .locked
+        .iter()
+        .filter(|(row, _)| !rows.contains(row))
+        .map(|&(row, col)| (row + rows.iter().filter(|&&r| r > row).count(), col))
+        .collect();
 
     for _ in 0..lines {
         gs.display
             .insert(0, vec![Tetrominoe::default(); gs.display[0].len()]); // add new line at the top
     }
 
-    match lines {
-        1 => gs.gamescore.score += 40 * (gs.gamescore.level + 1),
-        2 => gs.gamescore.score += 100 * (gs.gamescore.level + 1),
-        3 => gs.gamescore.score += 300 * (gs.gamescore.level + 1),
-        4 => gs.gamescore.score += 1200 * (gs.gamescore.level + 1),
-        _ => (),
-    }
-
+    let points = match lines {
+        1 => 40,
+        2 => 100,
+        3 => 300,
+        4 => 1200,
+        _ => 0,
+    } * (gs.gamescore.level + 1);
+    gs.gamescore.score += points;
+
+    gs.gamescore.lines += lines;
+    gs.stats.cleared(lines);
     gs.gamescore.level = gs.gamescore.score / 1000;
+
+    if lines > 0 {
+        let tspin = std::mem::take(&mut gs.events.tspin);
+        let difficult = tspin || lines == 4;
+        let b2b = difficult && gs.events.difficult;
+        gs.events.difficult = difficult;
+        let all_clear = gs
+            .display
+            .iter()
+            .flatten()
+            .all(|ch| ch.game_state != State::Landed);
+        gs.events.push(events::Event {
+            lines,
+            tspin,
+            combo: gs.stats.combo,
+            b2b,
+            all_clear,
+            points,
+        });
+    }
 }
 
 pub fn ghost_piece(gs: &mut GameState) {
@@ -509,66 +743,30 @@ fn gravity_until_new_piece(gs: &mut GameState) {
     gs.display = prev_display;
 }
 
-pub fn get_input() -> char {
+// block until a key is pressed; used by menus, which read keys rather than
+// actions. KeyCode::Null after a resize, for the screen to redraw itself at
+// the new position. None once a signal arrived: menus back out and leave
+// saving and exiting to the game loop
+pub fn read_key() -> Option<KeyCode> {
     loop {
-        if poll(Duration::from_millis(0)).unwrap() {
-            let input = event::read().unwrap();
-            match input {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('q'),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'q', // quit
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(' '),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 's', // hard drop
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('c'),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'c', // hold
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('p'),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'p', // pause
-                Event::Key(KeyEvent {
-                    code: KeyCode::Left,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'l', // move left
-                Event::Key(KeyEvent {
-                    code: KeyCode::Right,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'r', // move right
-                Event::Key(KeyEvent {
-                    code: KeyCode::Up,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'u', // rotate clockwise
-                Event::Key(KeyEvent {
-                    code: KeyCode::Down,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'd', // soft drop
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('y'),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'y', // yes
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('n'),
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => return 'n', // no
-
-                _ => (),
+        if signals::received().is_some() {
+            return None;
+        }
+        if !poll(Duration::from_millis(100)).unwrap() {
+            continue;
+        }
+        match event::read().unwrap() {
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) => return Some(code),
+            Event::Resize(..) => {
+                layout::resized();
+                layout::fit();
+                return Some(KeyCode::Null);
             }
-        } else {
-            return ' ';
+            _ => (),
         }
     }
 }
@@ -597,51 +795,138 @@ pub fn hold(gs: &mut GameState) {
 fn get_next_piece(gs: &mut GameState) -> char {
     let temp = gs.next_piece.ptype;
     gs.next_piece = Tetrominoe::random(&mut gs.bag);
+    gs.stats.spawned(temp);
     temp
 }
 
 pub fn put_text(width: u16, height: u16, text: &str) {
-    let mut stdout = stdout();
+    let text_width = width as usize * 2;
+    let menu = theme::current().menu;
+    renderer::with(|out| {
+        // top bar
+        out.print(
+            width + 3,
+            height / 2 - 2,
+            &"=".repeat(text_width),
+            menu,
+            false,
+        );
+        out.print(
+            width + 3,
+            height / 2 - 1,
+            &" ".repeat(text_width),
+            Color::Reset,
+            false,
+        );
+
+        // text
+        out.print(
+            width + 3,
+            height / 2,
+            &format!("{:^text_width$}", text),
+            menu,
+            false,
+        );
+
+        out.print(
+            width + 3,
+            height / 2 + 1,
+            &" ".repeat(text_width),
+            Color::Reset,
+            false,
+        );
+
+        // bottom bar
+        out.print(
+            width + 3,
+            height / 2 + 2,
+            &"=".repeat(text_width),
+            menu,
+            false,
+        );
+        out.flush();
+    });
+}
+
+// ask for a short name (letters, digits, '-' and '_'); None if cancelled with Esc
+pub fn prompt_text(width: u16, height: u16, prompt: &str) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        put_text(width, height, &format!("{}: {}_", prompt, text));
+        match read_key() {
+            Some(KeyCode::Enter) if !text.is_empty() => return Some(text),
+            Some(KeyCode::Esc) | None => return None,
+            Some(KeyCode::Backspace) => {
+                text.pop();
+            }
+            Some(KeyCode::Char(c))
+                if (c.is_ascii_alphanumeric() || c == '-' || c == '_') && text.len() < 12 =>
+            {
+                text.push(c)
+            }
+            _ => (),
+        }
+    }
+}
 
-    // top bar
-    stdout.queue(MoveTo(width + 3, height / 2 - 2)).unwrap();
-    stdout
-        .queue(SetForegroundColor(Color::Red))
-        .unwrap()
-        .queue(Print("=".repeat(width as usize * 2)))
-        .unwrap()
-        .queue(ResetColor)
-        .unwrap();
-
-    stdout.queue(MoveTo(width + 3, height / 2 - 1)).unwrap();
-    stdout.queue(Print(" ".repeat(width as usize * 2))).unwrap();
-
-    // text
-    stdout.queue(MoveTo(width + 3, height / 2)).unwrap();
-    stdout
-        .queue(SetForegroundColor(Color::Red))
-        .unwrap()
-        .queue(Print(format!(
-            "{:^text_width$}",
-            text,
-            text_width = width as usize * 2
-        )))
-        .unwrap()
-        .queue(ResetColor)
-        .unwrap();
-
-    stdout.queue(MoveTo(width + 3, height / 2 + 1)).unwrap();
-    stdout.queue(Print(" ".repeat(width as usize * 2))).unwrap();
-
-    // bottom bar
-    stdout.queue(MoveTo(width + 3, height / 2 + 2)).unwrap();
-    stdout
-        .queue(SetForegroundColor(Color::Red))
-        .unwrap()
-        .queue(Print("=".repeat(width as usize * 2)))
-        .unwrap()
-        .queue(ResetColor)
-        .unwrap();
-
-    stdout.flush().unwrap();
+// list of choices drawn over the middle of the board; returns the chosen
+// index, or None if dismissed
+pub fn menu(width: u16, height: u16, title: &str, items: &[&str]) -> Option<usize> {
+    let text_width = width as usize * 2;
+    let top = (height / 2).saturating_sub((items.len() as u16 + 5) / 2);
+    let mut selected = 0;
+    let menu = theme::current().menu;
+
+    loop {
+        renderer::with(|out| {
+            // top bar and title
+            out.print(width + 3, top, &"=".repeat(text_width), menu, false);
+            out.print(
+                width + 3,
+                top + 1,
+                &format!("{:^text_width$}", title),
+                menu,
+                false,
+            );
+            out.print(
+                width + 3,
+                top + 2,
+                &" ".repeat(text_width),
+                Color::Reset,
+                false,
+            );
+
+            // items
+            for (i, item) in items.iter().enumerate() {
+                out.print(
+                    width + 3,
+                    top + 3 + i as u16,
+                    &format!("{:^text_width$}", item),
+                    Color::Reset,
+                    i == selected,
+                );
+            }
+
+            // bottom bar
+            let bottom = top + 3 + items.len() as u16;
+            out.print(
+                width + 3,
+                bottom,
+                &" ".repeat(text_width),
+                Color::Reset,
+                false,
+            );
+            out.print(width + 3, bottom + 1, &"=".repeat(text_width), menu, false);
+            out.flush();
+        });
+
+        let code = read_key()?;
+        match keys::nav(code) {
+            Some(Nav::Up) => selected = (selected + items.len() - 1) % items.len(),
+            Some(Nav::Down) => selected = (selected + 1) % items.len(),
+            Some(Nav::Select) => return Some(selected),
+            Some(Nav::Back) => return None,
+            None => (),
+        }
+    }
 }
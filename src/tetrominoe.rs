@@ -1,4 +1,3 @@
-use crossterm::style::Color;
 use serde::{Deserialize, Serialize};
 
 use crate::{bag::Bag, tetlib::EMP};
@@ -253,21 +252,4 @@ impl Tetrominoe {
         let piece = bag.draw();
         Tetrominoe::from(piece, None)
     }
-
-    pub fn as_color(&self) -> Color {
-        match self.color {
-            TColor::Cyan => Color::Cyan,
-            TColor::Blue => Color::Blue,
-            TColor::Orange => Color::Rgb {
-                r: 255,
-                g: 127,
-                b: 0,
-            },
-            TColor::Yellow => Color::Yellow,
-            TColor::Red => Color::Red,
-            TColor::Magenta => Color::Magenta,
-            TColor::Green => Color::Green,
-            TColor::Empty => Color::Black,
-        }
-    }
 }
@@ -0,0 +1,606 @@
+use clap::ValueEnum;
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{Error, ErrorKind, Result},
+    sync::OnceLock,
+};
+
+use crate::args::parse_chars;
+use crate::tetrominoe::TColor;
+
+pub const BUILT_IN: [&str; 7] = [
+    "guideline",
+    "nes",
+    "gameboy",
+    "monochrome",
+    "solarized",
+    "okabe-ito",
+    "tol",
+];
+
+const PIECES: [char; 7] = ['I', 'J', 'L', 'O', 'S', 'T', 'Z'];
+
+// two columns per piece that can be told apart without colour
+const PATTERNS: [&str; 7] = ["##", "[]", "()", "<>", "{}", "%%", "@@"];
+
+#[derive(Clone)]
+pub struct Theme {
+    // colours of I, J, L, O, S, T and Z
+    pub pieces: [Color; 7],
+    pub ghost: Color,
+    // drawn for each ghost cell instead of the block characters
+    pub ghost_chars: String,
+    pub wall: Color,
+    pub hud: Color,
+    // bars and titles of menus and messages
+    pub menu: Color,
+    pub glyphs: Glyphs,
+}
+
+// how blocks of the pieces are drawn
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Glyphs {
+    // the block characters
+    Off,
+    // a different pattern for each piece
+    Pattern,
+    // the piece's letter, in reverse video
+    Letter,
+}
+
+// [themes.<name>] table of the config file; anything left out comes from
+// the base theme
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ThemeConfig {
+    base: Option<String>,
+    #[serde(default)]
+    pieces: BTreeMap<char, String>,
+    ghost: Option<String>,
+    ghost_chars: Option<String>,
+    wall: Option<String>,
+    hud: Option<String>,
+    menu: Option<String>,
+}
+
+// colours the terminal can show
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    // from NO_COLOR, COLORTERM and TERM
+    Auto,
+    Truecolor,
+    #[value(name = "256")]
+    #[serde(rename = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    #[serde(rename = "16")]
+    Ansi16,
+    None,
+}
+
+// the 16 colours with their usual xterm values; black is left out as it
+// would disappear on most backgrounds
+const ANSI16: [(Color, u32); 15] = [
+    (Color::DarkRed, 0xcd0000),
+    (Color::DarkGreen, 0x00cd00),
+    (Color::DarkYellow, 0xcdcd00),
+    (Color::DarkBlue, 0x0000ee),
+    (Color::DarkMagenta, 0xcd00cd),
+    (Color::DarkCyan, 0x00cdcd),
+    (Color::Grey, 0xe5e5e5),
+    (Color::DarkGrey, 0x7f7f7f),
+    (Color::Red, 0xff0000),
+    (Color::Green, 0x00ff00),
+    (Color::Yellow, 0xffff00),
+    (Color::Blue, 0x5c5cff),
+    (Color::Magenta, 0xff00ff),
+    (Color::Cyan, 0x00ffff),
+    (Color::White, 0xffffff),
+];
+
+// channel values of the 6x6x6 cube of the 256 colour palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorMode {
+    pub fn detect(self) -> Self {
+        if self != ColorMode::Auto {
+            return self;
+        }
+        ColorMode::from_env(|name| {
+            env::var_os(name).map(|value| value.to_string_lossy().into_owned())
+        })
+    }
+
+    // from NO_COLOR, COLORTERM and TERM, as looked up by `var`
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return ColorMode::None;
+        }
+        let colorterm = var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorMode::Truecolor;
+        }
+
+        let term = var("TERM").unwrap_or_default();
+        match term.as_str() {
+            // the Windows console doesn't set TERM
+            "" if cfg!(windows) => ColorMode::Truecolor,
+            "" | "dumb" => ColorMode::None,
+            _ if term.ends_with("direct") => ColorMode::Truecolor,
+            _ if term.contains("256color") => ColorMode::Ansi256,
+            _ => ColorMode::Ansi16,
+        }
+    }
+
+    // the closest colour this mode can show
+    pub fn fit(self, color: Color) -> Color {
+        let Color::Rgb { r, g, b } = color else {
+            return if self == ColorMode::None {
+                Color::Reset
+            } else {
+                color
+            };
+        };
+        match self {
+            ColorMode::Auto | ColorMode::Truecolor => color,
+            ColorMode::Ansi256 => Color::AnsiValue(ansi256(r, g, b)),
+            ColorMode::Ansi16 if (r, g, b) == (0, 0, 0) => Color::Black,
+            ColorMode::Ansi16 => {
+                ANSI16
+                    .iter()
+                    .min_by_key(|&&(_, hex)| distance((r, g, b), split(hex)))
+                    .unwrap()
+                    .0
+            }
+            ColorMode::None => Color::Reset,
+        }
+    }
+}
+
+fn split(hex: u32) -> (u8, u8, u8) {
+    ((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// nearest entry of the colour cube or the grey ramp
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE[ri], CUBE[gi], CUBE[bi]);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let step = (average.saturating_sub(8) / 10).min(23) as u8;
+    let grey = 8 + step * 10;
+
+    if distance((r, g, b), (grey, grey, grey)) < distance((r, g, b), cube) {
+        232 + step
+    } else {
+        (16 + 36 * ri + 6 * gi + bi) as u8
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+const fn rgb(hex: u32) -> Color {
+    Color::Rgb {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    }
+}
+
+impl Theme {
+    pub fn built_in(name: &str) -> Option<Self> {
+        let (pieces, ghost, wall, hud, menu) = match name {
+            "guideline" => (
+                [
+                    Color::Cyan,
+                    Color::Blue,
+                    rgb(0xff7f00),
+                    Color::Yellow,
+                    Color::Green,
+                    Color::Magenta,
+                    Color::Red,
+                ],
+                rgb(0x323232),
+                Color::Reset,
+                Color::Reset,
+                Color::Red,
+            ),
+            // level 0 of the NES version
+            "nes" => (
+                [
+                    rgb(0xfcfcfc),
+                    rgb(0x0058f8),
+                    rgb(0x3cbcfc),
+                    rgb(0xfcfcfc),
+                    rgb(0x0058f8),
+                    rgb(0xfcfcfc),
+                    rgb(0x3cbcfc),
+                ],
+                rgb(0x404040),
+                rgb(0x747474),
+                rgb(0xfcfcfc),
+                rgb(0xf83800),
+            ),
+            "gameboy" => (
+                [
+                    rgb(0x9bbc0f),
+                    rgb(0x8bac0f),
+                    rgb(0x9bbc0f),
+                    rgb(0x8bac0f),
+                    rgb(0x9bbc0f),
+                    rgb(0x8bac0f),
+                    rgb(0x9bbc0f),
+                ],
+                rgb(0x306230),
+                rgb(0x8bac0f),
+                rgb(0x9bbc0f),
+                rgb(0x9bbc0f),
+            ),
+            "monochrome" => (
+                [Color::White; 7],
+                rgb(0x323232),
+                Color::Reset,
+                Color::Reset,
+                Color::Reset,
+            ),
+            "solarized" => (
+                [
+                    rgb(0x2aa198),
+                    rgb(0x268bd2),
+                    rgb(0xcb4b16),
+                    rgb(0xb58900),
+                    rgb(0x859900),
+                    rgb(0xd33682),
+                    rgb(0xdc322f),
+                ],
+                rgb(0x586e75),
+                rgb(0x657b83),
+                rgb(0x93a1a1),
+                rgb(0xdc322f),
+            ),
+            // colour-blind safe palettes by Masataka Okabe and Kei Ito, and
+            // by Paul Tol
+            "okabe-ito" => (
+                [
+                    rgb(0x56b4e9),
+                    rgb(0x0072b2),
+                    rgb(0xe69f00),
+                    rgb(0xf0e442),
+                    rgb(0x009e73),
+                    rgb(0xcc79a7),
+                    rgb(0xd55e00),
+                ],
+                rgb(0x404040),
+                Color::Reset,
+                Color::Reset,
+                rgb(0xd55e00),
+            ),
+            "tol" => (
+                [
+                    rgb(0x66ccee),
+                    rgb(0x4477aa),
+                    rgb(0xbbbbbb),
+                    rgb(0xccbb44),
+                    rgb(0x228833),
+                    rgb(0xaa3377),
+                    rgb(0xee6677),
+                ],
+                rgb(0x404040),
+                Color::Reset,
+                Color::Reset,
+                rgb(0xee6677),
+            ),
+            _ => return None,
+        };
+        Some(Theme {
+            pieces,
+            ghost,
+            ghost_chars: "//".to_string(),
+            wall,
+            hud,
+            menu,
+            glyphs: Glyphs::Off,
+        })
+    }
+
+    // a built-in theme or one from the config file
+    pub fn new(name: &str, themes: &BTreeMap<String, ThemeConfig>) -> Result<Self> {
+        Theme::resolve(name, themes, 0)
+    }
+
+    fn resolve(name: &str, themes: &BTreeMap<String, ThemeConfig>, depth: usize) -> Result<Self> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+
+        let Some(config) = themes.get(name) else {
+            return Theme::built_in(name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown theme {} (built in: {})",
+                    name,
+                    BUILT_IN.join(", ")
+                ))
+            });
+        };
+        // a theme can't be its own base, directly or through others
+        if depth > themes.len() {
+            return Err(invalid(format!("theme {} is based on itself", name)));
+        }
+
+        let base = config.base.as_deref().unwrap_or("guideline");
+        let mut theme = if base == name {
+            Theme::built_in(base).ok_or_else(|| invalid(format!("unknown theme {}", base)))?
+        } else {
+            Theme::resolve(base, themes, depth + 1)?
+        };
+
+        let color = |value: &str| {
+            parse_color(value).ok_or_else(|| invalid(format!("unknown colour {}", value)))
+        };
+        for (piece, value) in &config.pieces {
+            let i = PIECES
+                .iter()
+                .position(|p| p == piece)
+                .ok_or_else(|| invalid(format!("unknown piece {}", piece)))?;
+            theme.pieces[i] = color(value)?;
+        }
+        if let Some(value) = &config.ghost {
+            theme.ghost = color(value)?;
+        }
+        if let Some(chars) = &config.ghost_chars {
+            theme.ghost_chars =
+                parse_chars(chars).map_err(|e| invalid(format!("ghost-chars: {}", e)))?;
+        }
+        if let Some(value) = &config.wall {
+            theme.wall = color(value)?;
+        }
+        if let Some(value) = &config.hud {
+            theme.hud = color(value)?;
+        }
+        if let Some(value) = &config.menu {
+            theme.menu = color(value)?;
+        }
+        Ok(theme)
+    }
+
+    // every colour brought down to what the terminal can show
+    pub fn fit(mut self, mode: ColorMode) -> Self {
+        for color in self.pieces.iter_mut() {
+            *color = mode.fit(*color);
+        }
+        self.ghost = mode.fit(self.ghost);
+        self.wall = mode.fit(self.wall);
+        self.hud = mode.fit(self.hud);
+        self.menu = mode.fit(self.menu);
+        self
+    }
+
+    pub fn piece(&self, color: TColor) -> Color {
+        match index(color) {
+            Some(i) => self.pieces[i],
+            None => Color::Black,
+        }
+    }
+
+    // what to draw for one block of the piece with this colour
+    pub fn block(&self, color: TColor, chars: &str) -> String {
+        match (self.glyphs, index(color)) {
+            (Glyphs::Pattern, Some(i)) => PATTERNS[i].to_string(),
+            (Glyphs::Letter, Some(i)) => format!("{} ", PIECES[i]),
+            _ => chars.to_string(),
+        }
+    }
+}
+
+// position of the piece with this colour in PIECES
+fn index(color: TColor) -> Option<usize> {
+    match color {
+        TColor::Cyan => Some(0),
+        TColor::Blue => Some(1),
+        TColor::Orange => Some(2),
+        TColor::Yellow => Some(3),
+        TColor::Green => Some(4),
+        TColor::Magenta => Some(5),
+        TColor::Red => Some(6),
+        TColor::Empty => None,
+    }
+}
+
+pub fn set(theme: Theme) {
+    THEME.set(theme).ok();
+}
+
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| Theme::built_in("guideline").unwrap())
+}
+
+// "#rrggbb" or a terminal colour name like "cyan" or "dark-grey"
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        return u32::from_str_radix(hex, 16).ok().map(rgb);
+    }
+    Color::try_from(value.replace('-', "_").as_str()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> ColorMode {
+        ColorMode::from_env(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn detects_the_color_mode() {
+        assert_eq!(detect(&[("TERM", "xterm")]), ColorMode::Ansi16);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ColorMode::Ansi256);
+        assert_eq!(detect(&[("TERM", "xterm-direct")]), ColorMode::Truecolor);
+        assert_eq!(detect(&[("TERM", "dumb")]), ColorMode::None);
+        let unset = if cfg!(windows) {
+            ColorMode::Truecolor
+        } else {
+            ColorMode::None
+        };
+        assert_eq!(detect(&[]), unset);
+
+        // COLORTERM goes over TERM, NO_COLOR over both unless it is empty
+        let truecolor = [("TERM", "xterm"), ("COLORTERM", "truecolor")];
+        assert_eq!(detect(&truecolor), ColorMode::Truecolor);
+        assert_eq!(
+            detect(&[("TERM", "screen"), ("COLORTERM", "24bit")]),
+            ColorMode::Truecolor
+        );
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color"), ("COLORTERM", "yes")]),
+            ColorMode::Ansi256
+        );
+        assert_eq!(
+            detect(&[truecolor[0], truecolor[1], ("NO_COLOR", "1")]),
+            ColorMode::None
+        );
+        assert_eq!(
+            detect(&[truecolor[0], truecolor[1], ("NO_COLOR", "")]),
+            ColorMode::Truecolor
+        );
+
+        // a mode that was asked for is kept
+        assert_eq!(ColorMode::Ansi16.detect(), ColorMode::Ansi16);
+    }
+
+    #[test]
+    fn fits_the_256_color_palette() {
+        let fit = |hex| ColorMode::Ansi256.fit(rgb(hex));
+        assert_eq!(fit(0xff0000), Color::AnsiValue(196));
+        assert_eq!(fit(0x5f87af), Color::AnsiValue(67));
+        assert_eq!(fit(0x000000), Color::AnsiValue(16));
+        // greys go to the grey ramp rather than the cube
+        assert_eq!(fit(0x808080), Color::AnsiValue(244));
+        assert_eq!(fit(0x323232), Color::AnsiValue(236));
+
+        assert_eq!(ColorMode::Ansi256.fit(Color::Cyan), Color::Cyan);
+        assert_eq!(ColorMode::Truecolor.fit(rgb(0x123456)), rgb(0x123456));
+    }
+
+    #[test]
+    fn fits_the_16_color_palette() {
+        let fit = |hex| ColorMode::Ansi16.fit(rgb(hex));
+        assert_eq!(fit(0xff0000), Color::Red);
+        assert_eq!(fit(0xcd0000), Color::DarkRed);
+        assert_eq!(fit(0xff7f00), Color::DarkYellow);
+        assert_eq!(fit(0x0058f8), Color::DarkBlue);
+        assert_eq!(fit(0xfcfcfc), Color::White);
+        assert_eq!(fit(0x000000), Color::Black);
+
+        assert_eq!(ColorMode::Ansi16.fit(Color::Magenta), Color::Magenta);
+        assert_eq!(ColorMode::None.fit(Color::Magenta), Color::Reset);
+        assert_eq!(ColorMode::None.fit(rgb(0xff0000)), Color::Reset);
+    }
+
+    fn themes(text: &str) -> BTreeMap<String, ThemeConfig> {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn resolves_the_base_theme() {
+        let themes = themes(
+            r##"
+            [mine]
+            base = "nes"
+            wall = "#010203"
+            pieces = { T = "red" }
+
+            [yours]
+            base = "mine"
+            hud = "dark-grey"
+            menu = "#020304"
+
+            [plain]
+            ghost-chars = ".."
+
+            [gameboy]
+            base = "gameboy"
+            ghost = "white"
+            "##,
+        );
+        let nes = Theme::built_in("nes").unwrap();
+
+        let mine = Theme::new("mine", &themes).unwrap();
+        assert_eq!(mine.wall, rgb(0x010203));
+        assert_eq!(mine.pieces[5], Color::Red);
+        assert_eq!(mine.pieces[..5], nes.pieces[..5]);
+        assert_eq!(mine.hud, nes.hud);
+        assert_eq!(mine.menu, nes.menu);
+
+        // through another user theme
+        let yours = Theme::new("yours", &themes).unwrap();
+        assert_eq!(yours.wall, rgb(0x010203));
+        assert_eq!(yours.pieces[5], Color::Red);
+        assert_eq!(yours.hud, Color::DarkGrey);
+        assert_eq!(yours.menu, rgb(0x020304));
+
+        // guideline without a base, and the built-in theme of the same name
+        let plain = Theme::new("plain", &themes).unwrap();
+        assert_eq!(plain.ghost_chars, "..");
+        assert_eq!(plain.pieces, Theme::built_in("guideline").unwrap().pieces);
+        let gameboy = Theme::new("gameboy", &themes).unwrap();
+        assert_eq!(gameboy.ghost, Color::White);
+        assert_eq!(gameboy.wall, Theme::built_in("gameboy").unwrap().wall);
+    }
+
+    #[test]
+    fn rejects_bad_bases() {
+        let error =
+            |text: &str, name: &str| Theme::new(name, &themes(text)).err().unwrap().to_string();
+        assert!(error("[a]\nbase = \"b\"\n[b]\nbase = \"a\"", "a").contains("based on itself"));
+        assert!(error("[a]\nbase = \"nope\"", "a").starts_with("unknown theme nope"));
+        assert!(error("", "nope").starts_with("unknown theme nope"));
+        assert_eq!(
+            error("[a]\nwall = \"bright\"", "a"),
+            "unknown colour bright"
+        );
+        assert_eq!(
+            error("[a]\npieces = { X = \"red\" }", "a"),
+            "unknown piece X"
+        );
+
+        // ghost cells are two columns, like the blocks
+        assert!(error("[a]\nghost-chars = \"🟥🟥\"", "a").starts_with("ghost-chars: "));
+        assert!(error("[a]\nghost-chars = \"/\"", "a").starts_with("ghost-chars: "));
+        assert!(error("[a]\nghost-chars = \"a\u{301}b\"", "a").starts_with("ghost-chars: "));
+    }
+
+    #[test]
+    fn draws_built_in_themes_and_glyphs() {
+        for name in BUILT_IN {
+            assert!(Theme::new(name, &BTreeMap::new()).is_ok(), "{}", name);
+        }
+
+        let mut theme = Theme::built_in("okabe-ito").unwrap();
+        assert_eq!(theme.block(TColor::Magenta, "[]"), "[]");
+        theme.glyphs = Glyphs::Pattern;
+        assert_eq!(theme.block(TColor::Magenta, "[]"), "%%");
+        theme.glyphs = Glyphs::Letter;
+        assert_eq!(theme.block(TColor::Magenta, "[]"), "T ");
+        assert_eq!(theme.block(TColor::Empty, "[]"), "[]");
+    }
+}